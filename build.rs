@@ -1,7 +1,178 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
+use std::path::Path;
+use std::{env, fs};
+
+#[path = "src/pattern_format.rs"]
+mod pattern_format;
+
+use pattern_format::PatternBuf;
+
 fn main() {
     build_info_build::build_script();
 
     println!("cargo:rustc-link-arg-bins=--nmagic");
     println!("cargo:rustc-link-arg-bins=-Tlink.x");
     println!("cargo:rustc-link-arg-bins=-Tdefmt.x");
+
+    build_patterns();
+}
+
+/// Directory names of the pattern categories, as `Category` variants.
+const CATEGORIES: &[(&str, &str)] = &[
+    ("still", "StillLife"),
+    ("oscillator", "Oscillator"),
+    ("spaceship", "Spaceship"),
+];
+/// Longest period looked for when running a pattern, the most
+/// `Pattern::period` (a `u8`) holds.
+const MAX_PERIOD: usize = u8::MAX as usize;
+
+/// Pack every pattern under `patterns/<category>/` into a table included
+/// by `src/patterns.rs`, one bit per cell, along with the period and
+/// displacement found by running it under B3/S23.
+fn build_patterns() {
+    println!("cargo:rerun-if-changed=patterns");
+    println!("cargo:rerun-if-changed=src/pattern_format.rs");
+
+    let mut patterns = Vec::new();
+    for dir in sorted_entries(Path::new("patterns")) {
+        let category = CATEGORIES
+            .iter()
+            .find(|(name, _)| dir.is_dir() && file_stem(&dir) == *name)
+            .unwrap_or_else(|| panic!("{}: not a category directory", dir.display()))
+            .1;
+        for file in sorted_entries(&dir) {
+            let src = fs::read(&file).unwrap();
+            let parsed = match file.extension().and_then(|e| e.to_str()) {
+                Some("rle") => pattern_format::parse_rle(&src),
+                Some("cells") => pattern_format::parse_cells(&src),
+                _ => continue,
+            };
+            let pattern =
+                parsed.unwrap_or_else(|e| panic!("{}: bad pattern: {:?}", file.display(), e));
+            let (period, (dx, dy)) = run(&pattern)
+                .unwrap_or_else(|| panic!("{}: no period up to {}", file.display(), MAX_PERIOD));
+            let found = match (period, dx, dy) {
+                (1, _, _) => "StillLife",
+                (_, 0, 0) => "Oscillator",
+                _ => "Spaceship",
+            };
+            if found != category {
+                panic!(
+                    "{}: {} in the {} directory",
+                    file.display(),
+                    found,
+                    category
+                );
+            }
+            patterns.push((file_stem(&file), category, pattern, period, (dx, dy)));
+        }
+    }
+
+    let mut data = Vec::new();
+    let mut table = String::new();
+    for (name, category, pattern, period, (dx, dy)) in &patterns {
+        let offset = data.len();
+        pack(pattern, &mut data);
+        writeln!(
+            table,
+            "    Pattern {{ name: {:?}, category: Category::{}, period: {}, width: {}, height: {}, \
+             velocity: ({}, {}), offset: {} }},",
+            name, category, period, pattern.width, pattern.height, dx, dy, offset
+        )
+        .unwrap();
+    }
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "const PATTERN_DATA: [u8; {}] = {:?};",
+        data.len(),
+        data
+    )
+    .unwrap();
+    writeln!(out, "pub const PATTERNS: &[Pattern] = &[\n{}];", table).unwrap();
+    let dest = Path::new(&env::var("OUT_DIR").unwrap()).join("patterns.rs");
+    fs::write(dest, out).unwrap();
+}
+
+/// Append the cells row by row, bit `i % 8` of byte `i / 8` for cell `i`.
+fn pack(pattern: &PatternBuf, data: &mut Vec<u8>) {
+    let (w, h) = (pattern.width as usize, pattern.height as usize);
+    let mut bytes = vec![0u8; (w * h).div_ceil(8)];
+    for y in 0..h {
+        for x in 0..w {
+            if pattern.get(x, y) {
+                let i = y * w + x;
+                bytes[i / 8] |= 1 << (i % 8);
+            }
+        }
+    }
+    data.extend(bytes);
+}
+
+/// Run `pattern` under B3/S23 until it comes back to its first phase,
+/// returning the period and how far it moved meanwhile.
+fn run(pattern: &PatternBuf) -> Option<(usize, (i32, i32))> {
+    let mut cells = HashSet::new();
+    for y in 0..pattern.height as i32 {
+        for x in 0..pattern.width as i32 {
+            if pattern.get(x as usize, y as usize) {
+                cells.insert((x, y));
+            }
+        }
+    }
+    let (first, origin) = normalize(&cells);
+    for period in 1..=MAX_PERIOD {
+        cells = step(&cells);
+        if cells.is_empty() {
+            return None;
+        }
+        let (phase, at) = normalize(&cells);
+        if phase == first {
+            return Some((period, (at.0 - origin.0, at.1 - origin.1)));
+        }
+    }
+    None
+}
+
+fn step(cells: &HashSet<(i32, i32)>) -> HashSet<(i32, i32)> {
+    let mut counts = HashMap::new();
+    for &(x, y) in cells {
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                if dx != 0 || dy != 0 {
+                    *counts.entry((x + dx, y + dy)).or_insert(0) += 1;
+                }
+            }
+        }
+    }
+    counts
+        .into_iter()
+        .filter(|(c, n)| *n == 3 || *n == 2 && cells.contains(c))
+        .map(|(c, _)| c)
+        .collect()
+}
+
+/// The cells moved to the origin, sorted, and where they were moved from.
+fn normalize(cells: &HashSet<(i32, i32)>) -> (Vec<(i32, i32)>, (i32, i32)) {
+    let x0 = cells.iter().map(|c| c.0).min().unwrap_or(0);
+    let y0 = cells.iter().map(|c| c.1).min().unwrap_or(0);
+    let mut v: Vec<_> = cells.iter().map(|(x, y)| (x - x0, y - y0)).collect();
+    v.sort();
+    (v, (x0, y0))
+}
+
+fn sorted_entries(dir: &Path) -> Vec<std::path::PathBuf> {
+    let mut entries: Vec<_> = fs::read_dir(dir)
+        .unwrap_or_else(|e| panic!("{}: {}", dir.display(), e))
+        .map(|e| e.unwrap().path())
+        .collect();
+    entries.sort();
+    entries
+}
+
+fn file_stem(path: &Path) -> String {
+    path.file_stem().unwrap().to_string_lossy().into_owned()
 }
@@ -0,0 +1,265 @@
+use crate::{DisplayConfig, ICN2037Message, PixelAddr};
+
+/// 16-plane framebuffer shared by every output mode.
+///
+/// Each gray level is spread over the planes by `LUT16`, and the planes are
+/// shifted out one after another by a `PlaneOutput`.
+pub struct Bitplanes<'d> {
+    pub config: DisplayConfig,
+    pub buffer: &'d mut [u16],
+    lut: &'d mut [PixelAddr],
+    /// Per pixel correction, indexed like `lut`, 255 leaves a pixel as is.
+    calibration: Option<&'d [u8]>,
+    /// Pixels forced off, one bit per pixel indexed like `lut`.
+    mask: Option<&'d mut [u32]>,
+    max_brightness: u8,
+    /// Runs of identical consecutive planes as (first plane, length).
+    plane_runs: [(u8, u8); 16],
+    plane_run_count: usize,
+    planes_dirty: bool,
+}
+
+impl<'d> Bitplanes<'d> {
+    pub fn new(config: DisplayConfig, buffer: &'d mut [u16], lut: &'d mut [PixelAddr]) -> Self {
+        config.build_lut(lut);
+        Self {
+            config,
+            buffer,
+            lut,
+            calibration: None,
+            mask: None,
+            max_brightness: 15,
+            plane_runs: [(0, 0); 16],
+            plane_run_count: 0,
+            planes_dirty: true,
+        }
+    }
+
+    pub fn set_brightness(&mut self, brightness: u8) {
+        self.max_brightness = brightness;
+    }
+
+    /// Scale every pixel by `table[x * height + y] / 255` before it is
+    /// spread over the planes, to even out brighter and dimmer LEDs.
+    pub fn set_calibration(&mut self, table: &'d [u8]) {
+        assert!(table.len() >= self.config.width * self.config.height);
+        self.calibration = Some(table);
+    }
+
+    /// Use `mask` to record dead or stuck pixels, which are then kept off
+    /// so shorted LEDs do not draw current. Pixels already set in `mask`,
+    /// such as a mask loaded from flash, are turned off right away.
+    pub fn set_pixel_mask(&mut self, mask: &'d mut [u32]) {
+        assert!(mask.len() * 32 >= self.config.width * self.config.height);
+        self.mask = Some(mask);
+        self.apply_mask();
+        self.planes_dirty = true;
+    }
+
+    pub fn is_masked(&self, x: usize, y: usize) -> bool {
+        let i = x * self.config.height + y;
+        match &self.mask {
+            Some(mask) => mask[i / 32] & (1 << (i % 32)) != 0,
+            None => false,
+        }
+    }
+
+    pub fn mask_pixel(&mut self, x: usize, y: usize, masked: bool) {
+        if x >= self.config.width || y >= self.config.height {
+            return;
+        }
+        let i = x * self.config.height + y;
+        let Some(mask) = &mut self.mask else {
+            return;
+        };
+        if masked {
+            mask[i / 32] |= 1 << (i % 32);
+            self.set_pixel_gray(x, y, 0);
+        } else {
+            mask[i / 32] &= !(1 << (i % 32));
+        }
+    }
+
+    /// Turn the masked pixels off in every plane.
+    fn apply_mask(&mut self) {
+        let sz = self.frame_buffer_len();
+        let pixels = self.config.width * self.config.height;
+        let Some(mask) = &self.mask else {
+            return;
+        };
+        for (i, addr) in self.lut[..pixels].iter().enumerate() {
+            if mask[i / 32] & (1 << (i % 32)) != 0 {
+                for k in 0..16 {
+                    self.buffer[k * sz + addr.idx as usize] &= !addr.mask;
+                }
+            }
+        }
+    }
+
+    pub fn frame_buffer_len(&self) -> usize {
+        self.config.width * self.config.height / 16
+    }
+
+    pub fn plane(&self, k: usize) -> &[u16] {
+        let sz = self.frame_buffer_len();
+        &self.buffer[k * sz..(k + 1) * sz]
+    }
+
+    pub fn clear(&mut self) {
+        self.buffer.iter_mut().for_each(|x| *x = 0);
+        self.planes_dirty = true;
+    }
+
+    /// Must be called after writing `buffer` directly, so that the plane
+    /// schedule is rebuilt before the next frame.
+    pub fn invalidate_planes(&mut self) {
+        self.planes_dirty = true;
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, value: bool, buffer_offset: usize) {
+        if x >= self.config.width || y >= self.config.height {
+            return;
+        }
+        let PixelAddr { idx, mask } = self.lut[x * self.config.height + y];
+        let value = value && !self.is_masked(x, y);
+        let b = &mut self.buffer[idx as usize + buffer_offset];
+        if value {
+            *b |= mask;
+        } else {
+            *b &= !mask;
+        }
+        self.planes_dirty = true;
+    }
+
+    pub fn set_pixel_gray(&mut self, x: usize, y: usize, value: u8) {
+        if x >= self.config.width || y >= self.config.height {
+            return;
+        }
+        let i = x * self.config.height + y;
+        let PixelAddr { idx, mask } = self.lut[i];
+        let mut value = value.min(self.max_brightness);
+        if self.is_masked(x, y) {
+            value = 0;
+        }
+        if let Some(calibration) = self.calibration {
+            value = ((value as u16 * calibration[i] as u16 + 127) / 255) as u8;
+        }
+        let planes = LEVEL_PLANES[value as usize];
+        let sz = self.frame_buffer_len();
+        let mut w = idx as usize;
+        for k in 0..16 {
+            let b = &mut self.buffer[w];
+            if planes & (1 << k) != 0 {
+                *b |= mask;
+            } else {
+                *b &= !mask;
+            }
+            w += sz;
+        }
+        self.planes_dirty = true;
+    }
+
+    /// Runs of identical consecutive planes as (first plane, length),
+    /// rebuilt only when the buffer changed since the last call.
+    pub fn plane_runs(&mut self) -> &[(u8, u8)] {
+        if self.planes_dirty {
+            let sz = self.frame_buffer_len();
+            let mut count = 0;
+            let mut start = 0;
+            for k in 1..=16 {
+                if k == 16
+                    || self.buffer[k * sz..(k + 1) * sz]
+                        != self.buffer[start * sz..(start + 1) * sz]
+                {
+                    self.plane_runs[count] = (start as u8, (k - start) as u8);
+                    count += 1;
+                    start = k;
+                }
+            }
+            self.plane_run_count = count;
+            self.planes_dirty = false;
+        }
+        &self.plane_runs[..self.plane_run_count]
+    }
+
+    pub fn handle_message(&mut self, msg: ICN2037Message) {
+        match msg {
+            ICN2037Message::SetPixel((x, y, v)) => self.set_pixel_gray(x, y, v),
+            ICN2037Message::FillPixels((sx, sy, ex, ey, v)) => {
+                for x in sx..ex {
+                    for y in sy..ey {
+                        self.set_pixel_gray(x, y, v);
+                    }
+                }
+            }
+            ICN2037Message::Buffer(b) => {
+                // copy buffers
+                let len = b.len().min(self.buffer.len());
+                unsafe {
+                    core::ptr::copy_nonoverlapping(b.as_ptr(), self.buffer.as_mut_ptr(), len);
+                }
+                // raw planes skip calibration, but masked pixels stay off
+                self.apply_mask();
+                self.planes_dirty = true;
+            }
+            ICN2037Message::Pixels(pixels) => {
+                let ex = self.config.width.min(pixels.len());
+                let ey = self.config.height.min(pixels[0].len());
+                for x in 0..ex {
+                    for y in 0..ey {
+                        self.set_pixel_gray(x, y, pixels[x][y]);
+                    }
+                }
+            }
+            ICN2037Message::PixelsFrame(frame) => {
+                for x in 0..25 {
+                    for y in 0..16 {
+                        self.set_pixel_gray(x, y, frame[x][y]);
+                    }
+                }
+            }
+            ICN2037Message::Fullfill(brightness) => {
+                for x in 0..self.config.width {
+                    for y in 0..self.config.height {
+                        self.set_pixel_gray(x, y, brightness);
+                    }
+                }
+            }
+            ICN2037Message::Clear => self.clear(),
+        }
+    }
+}
+
+const LUT16: [[u8; 16]; 16] = [
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], // 0
+    [1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], // 1
+    [1, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0], // 2
+    [1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0], // 3
+    [1, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0], // 4
+    [1, 0, 0, 1, 0, 0, 0, 1, 0, 0, 1, 0, 0, 1, 0, 0], // 5
+    [1, 0, 0, 1, 0, 0, 1, 0, 0, 1, 0, 0, 1, 0, 1, 0], // 6
+    [1, 0, 1, 0, 1, 0, 0, 1, 0, 0, 1, 0, 1, 0, 1, 0], // 7
+    [1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0], // 8
+    [1, 1, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0], // 9
+    [1, 1, 1, 0, 1, 0, 1, 0, 1, 1, 1, 0, 1, 0, 1, 0], // 10
+    [1, 1, 1, 0, 1, 1, 1, 0, 1, 1, 1, 0, 1, 0, 1, 0], // 11
+    [1, 1, 1, 0, 1, 1, 1, 0, 1, 1, 1, 0, 1, 1, 1, 0], // 12
+    [1, 1, 1, 0, 1, 1, 1, 1, 1, 1, 1, 0, 1, 1, 1, 1], // 13
+    [1, 1, 1, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1], // 14
+    [1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1], // 15
+];
+
+/// `LUT16` packed per level, bit `k` set when the level is lit in plane `k`.
+const LEVEL_PLANES: [u16; 16] = {
+    let mut planes = [0u16; 16];
+    let mut level = 0;
+    while level < 16 {
+        let mut k = 0;
+        while k < 16 {
+            planes[level] |= (LUT16[level][k] as u16) << k;
+            k += 1;
+        }
+        level += 1;
+    }
+    planes
+};
@@ -0,0 +1,161 @@
+use core::marker::PhantomData;
+
+use embassy_time::{block_for, Duration};
+use embedded_hal::digital::OutputPin;
+use embedded_hal::spi::SpiBus;
+
+use crate::chip::{Icn2037, LedChip};
+use crate::{Bitplanes, ChainPins, DisplayConfig, Error, LedDisplay, PixelAddr, PlaneOutput};
+
+/// A static (non-scanned) daisy chain of `C` chips, ICN2037 by default.
+pub struct Chain<SPI, OE, LE, C = Icn2037> {
+    spi: SPI,
+    oe: OE,
+    le: LE,
+    chip: PhantomData<C>,
+}
+
+impl<SPI, OE, LE, C> Chain<SPI, OE, LE, C>
+where
+    OE: OutputPin,
+    LE: OutputPin,
+    C: LedChip,
+{
+    pub fn new(spi: SPI, oe: OE, le: LE) -> Self {
+        Self {
+            spi,
+            oe,
+            le,
+            chip: PhantomData,
+        }
+    }
+
+    pub fn latch(&mut self) -> Result<(), Error> {
+        self.le.set_high().map_err(|_| Error::PinError)?;
+        if C::LATCH_PULSE.as_ticks() > 0 {
+            block_for(C::LATCH_PULSE);
+        }
+        self.le.set_low().map_err(|_| Error::PinError)?;
+        Ok(())
+    }
+
+    fn set_latch(&mut self, high: bool) -> Result<(), Error> {
+        if high {
+            self.le.set_high().map_err(|_| Error::PinError)
+        } else {
+            self.le.set_low().map_err(|_| Error::PinError)
+        }
+    }
+
+    fn set_output_enable(&mut self, enabled: bool) -> Result<(), Error> {
+        if enabled == C::OE_ACTIVE_LOW {
+            self.oe.set_low().map_err(|_| Error::PinError)
+        } else {
+            self.oe.set_high().map_err(|_| Error::PinError)
+        }
+    }
+}
+
+impl<SPI, OE, LE, C> Chain<SPI, OE, LE, C>
+where
+    SPI: SpiBus,
+    OE: OutputPin,
+    LE: OutputPin,
+    C: LedChip,
+{
+    pub fn write_16b(&mut self, data: u16) -> Result<(), Error> {
+        self.spi
+            .write(&data.to_be_bytes())
+            .map_err(|_| Error::BusError)?;
+        if C::LATCH_PER_WORD {
+            self.latch()?;
+        }
+        Ok(())
+    }
+}
+
+/// For `MultiChain`, with the SPI set up for 16-bit words, MSB first.
+impl<SPI, OE, LE, C> ChainPins for Chain<SPI, OE, LE, C>
+where
+    SPI: embedded_hal_async::spi::SpiBus<u16>,
+    OE: OutputPin,
+    LE: OutputPin,
+    C: LedChip,
+{
+    async fn write_words(&mut self, words: &[u16]) -> Result<(), Error> {
+        self.spi.write(words).await.map_err(|_| Error::BusError)
+    }
+
+    fn set_latch(&mut self, high: bool) -> Result<(), Error> {
+        Chain::set_latch(self, high)
+    }
+
+    fn set_output_enable(&mut self, enabled: bool) -> Result<(), Error> {
+        Chain::set_output_enable(self, enabled)
+    }
+
+    fn latch_per_word(&self) -> bool {
+        C::LATCH_PER_WORD
+    }
+
+    fn latch_pulse(&self) -> Duration {
+        C::LATCH_PULSE
+    }
+
+    fn oe_blanking(&self) -> Duration {
+        C::OE_BLANKING
+    }
+}
+
+impl<SPI, OE, LE, C> PlaneOutput for Chain<SPI, OE, LE, C>
+where
+    SPI: SpiBus,
+    OE: OutputPin,
+    LE: OutputPin,
+    C: LedChip,
+{
+    fn start(&mut self) -> Result<(), Error> {
+        self.set_output_enable(false)?;
+        self.le.set_low().map_err(|_| Error::PinError)?;
+        Ok(())
+    }
+
+    fn flush_plane(&mut self, plane: &[u16]) -> Result<(), Error> {
+        for w in plane {
+            self.write_16b(*w)?;
+        }
+        self.set_output_enable(false)?;
+        if !C::LATCH_PER_WORD {
+            self.latch()?;
+        }
+        if C::OE_BLANKING.as_ticks() > 0 {
+            block_for(C::OE_BLANKING);
+        }
+        self.set_output_enable(true)
+    }
+
+    fn blank(&mut self) -> Result<(), Error> {
+        self.set_output_enable(false)
+    }
+}
+
+pub type ICN2037<'d, SPI, OE, LE> = LedDisplay<'d, Chain<SPI, OE, LE, Icn2037>>;
+
+impl<'d, SPI, OE, LE, C> LedDisplay<'d, Chain<SPI, OE, LE, C>>
+where
+    SPI: SpiBus,
+    OE: OutputPin,
+    LE: OutputPin,
+    C: LedChip,
+{
+    pub fn new(
+        spi: SPI,
+        oe: OE,
+        le: LE,
+        config: DisplayConfig,
+        buffer: &'d mut [u16],
+        lut: &'d mut [PixelAddr],
+    ) -> Self {
+        Self::with_output(Chain::new(spi, oe, le), Bitplanes::new(config, buffer, lut))
+    }
+}
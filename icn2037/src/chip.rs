@@ -0,0 +1,53 @@
+use embassy_time::Duration;
+
+/// Signalling of one family of constant current sink drivers, chips with a
+/// serial shift register, a latch input and an output enable input.
+///
+/// Frames are always handled as 16-bit words, so an 8-bit part like the
+/// 74HC595 counts as a pair of chained chips.
+pub trait LedChip {
+    /// Latch after every shifted word instead of once per plane.
+    const LATCH_PER_WORD: bool;
+    /// How long the latch input is held high, zero for a plain pin toggle.
+    const LATCH_PULSE: Duration;
+    /// Whether outputs are on while `OE` is low.
+    const OE_ACTIVE_LOW: bool;
+    /// How long outputs stay off between two planes.
+    const OE_BLANKING: Duration;
+}
+
+/// ICN2037/ICN2038, latched after every word as the original boards do.
+pub struct Icn2037;
+impl LedChip for Icn2037 {
+    const LATCH_PER_WORD: bool = true;
+    const LATCH_PULSE: Duration = Duration::from_ticks(0);
+    const OE_ACTIVE_LOW: bool = true;
+    const OE_BLANKING: Duration = Duration::from_ticks(0);
+}
+
+/// Macroblock MBI5024.
+pub struct Mbi5024;
+impl LedChip for Mbi5024 {
+    const LATCH_PER_WORD: bool = false;
+    const LATCH_PULSE: Duration = Duration::from_ticks(0);
+    const OE_ACTIVE_LOW: bool = true;
+    const OE_BLANKING: Duration = Duration::from_ticks(0);
+}
+
+/// TI TLC5925, needs a slightly longer `OE` off time between planes.
+pub struct Tlc5925;
+impl LedChip for Tlc5925 {
+    const LATCH_PER_WORD: bool = false;
+    const LATCH_PULSE: Duration = Duration::from_ticks(0);
+    const OE_ACTIVE_LOW: bool = true;
+    const OE_BLANKING: Duration = Duration::from_micros(1);
+}
+
+/// Chains of 74HC595 shift registers, `LE` on `RCLK`.
+pub struct Hc595;
+impl LedChip for Hc595 {
+    const LATCH_PER_WORD: bool = false;
+    const LATCH_PULSE: Duration = Duration::from_micros(1);
+    const OE_ACTIVE_LOW: bool = true;
+    const OE_BLANKING: Duration = Duration::from_ticks(0);
+}
@@ -1,14 +1,26 @@
-#![no_std]
+#![cfg_attr(not(feature = "std"), no_std)]
 
 use core::future::Future;
 
 use embassy_sync::blocking_mutex::raw::NoopRawMutex;
 use embassy_sync::channel::{Receiver, Sender};
-use embassy_time::Timer;
+use embassy_time::{block_for, Duration, Instant, Timer};
 use embedded_graphics_core::geometry::Dimensions;
 use embedded_graphics_core::pixelcolor::IntoStorage;
-use embedded_hal::digital::OutputPin;
-use embedded_hal::spi::SpiBus;
+#[macro_use]
+mod fmt;
+
+mod bitplane;
+mod chain;
+pub mod chip;
+mod multi;
+mod scan;
+
+pub use bitplane::Bitplanes;
+pub use chain::{Chain, ICN2037};
+pub use chip::LedChip;
+pub use multi::{ChainPins, MultiChain, MultiICN2037};
+pub use scan::{ScanChain, ScanConfig, ScanICN2037};
 
 #[derive(Debug)]
 pub enum Error {
@@ -36,182 +48,244 @@ impl DisplayConfig {
             map_pixel,
         }
     }
+
+    /// Fill `lut` with the address of every pixel, indexed by `x * height + y`.
+    pub fn build_lut(&self, lut: &mut [PixelAddr]) {
+        assert!(lut.len() >= self.width * self.height);
+        for x in 0..self.width {
+            for y in 0..self.height {
+                let (idx, offset) = (self.map_pixel)(self, x, y);
+                lut[x * self.height + y] = PixelAddr {
+                    idx: idx as u16,
+                    mask: 1 << offset,
+                };
+            }
+        }
+    }
 }
 
-pub struct ICN2037<'d, SPI, OE, LE> {
-    spi: SPI,
-    oe: OE,
-    le: LE,
-    pub config: DisplayConfig,
-    pub buffer: &'d mut [u16],
-    max_brightness: u8,
+/// Word index and bit mask of one pixel inside a single bitplane.
+#[derive(Debug, Default, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PixelAddr {
+    pub idx: u16,
+    pub mask: u16,
 }
 
-impl<'d, SPI, OE, LE> ICN2037<'d, SPI, OE, LE>
+/// Where the bitplanes go, one plane at a time.
+pub trait PlaneOutput {
+    fn start(&mut self) -> Result<(), Error>;
+    fn flush_plane(&mut self, plane: &[u16]) -> Result<(), Error>;
+    /// Turn all outputs off.
+    fn blank(&mut self) -> Result<(), Error>;
+    /// Whether a flushed plane stays lit on its own until the next flush,
+    /// which is not the case for scanned panels.
+    fn holds_plane(&self) -> bool {
+        true
+    }
+}
+
+/// Bitplane framebuffer plus the output it is refreshed on.
+pub struct LedDisplay<'d, O> {
+    out: O,
+    pub planes: Bitplanes<'d>,
+    powered: bool,
+    /// Time the last single plane flush took, used to hold merged planes.
+    plane_time: Duration,
+}
+
+impl<'d, O> LedDisplay<'d, O>
 where
-    SPI: SpiBus,
-    OE: OutputPin,
-    LE: OutputPin,
+    O: PlaneOutput,
 {
-    pub fn new(spi: SPI, oe: OE, le: LE, config: DisplayConfig, buffer: &'d mut [u16]) -> Self {
+    pub fn with_output(out: O, planes: Bitplanes<'d>) -> Self {
         Self {
-            spi,
-            oe,
-            le,
-            config,
-            buffer,
-            max_brightness: 15,
+            out,
+            planes,
+            powered: true,
+            plane_time: Duration::from_ticks(0),
         }
     }
 
+    pub fn config(&self) -> &DisplayConfig {
+        &self.planes.config
+    }
+
     pub fn set_brightness(&mut self, brightness: u8) {
-        self.max_brightness = brightness;
+        self.planes.set_brightness(brightness);
     }
 
-    pub fn start(&mut self) -> Result<(), Error> {
-        self.oe.set_high().map_err(|_| Error::PinError)?;
-        self.le.set_low().map_err(|_| Error::PinError)?;
-        Ok(())
+    pub fn set_calibration(&mut self, table: &'d [u8]) {
+        self.planes.set_calibration(table);
     }
 
-    pub fn write_16b(&mut self, data: u16) -> Result<(), Error> {
-        let buf = data.to_be_bytes();
-        self.spi.write(&buf).map_err(|_| Error::BusError)?;
-        // latch
-        self.le.set_high().map_err(|_| Error::PinError)?;
-        self.le.set_low().map_err(|_| Error::PinError)?;
-        Ok(())
+    pub fn set_pixel_mask(&mut self, mask: &'d mut [u32]) {
+        self.planes.set_pixel_mask(mask);
+    }
+
+    pub fn start(&mut self) -> Result<(), Error> {
+        self.out.start()
     }
 
     pub fn frame_buffer_len(&self) -> usize {
-        self.config.width * self.config.height / 16
+        self.planes.frame_buffer_len()
     }
 
     pub fn flush(&mut self, buffer_offset: usize) -> Result<(), Error> {
-        let len = self.buffer.len().min(self.frame_buffer_len());
-        for i in buffer_offset..(len + buffer_offset) {
-            self.write_16b(self.buffer[i])?;
-        }
-        self.oe.set_high().map_err(|_| Error::PinError)?;
-        self.oe.set_low().map_err(|_| Error::PinError)?;
-        Ok(())
+        let sz = self.frame_buffer_len();
+        self.out
+            .flush_plane(&self.planes.buffer[buffer_offset..buffer_offset + sz])
     }
 
     pub fn clear(&mut self) {
-        self.buffer.iter_mut().for_each(|x| *x = 0);
+        self.planes.clear();
     }
 
     pub fn set_pixel(&mut self, x: usize, y: usize, value: bool, buffer_offset: usize) {
-        if x >= self.config.width || y >= self.config.height {
-            return;
-        }
-        let (idx, offset) = (self.config.map_pixel)(&self.config, x, y);
-        let b = &mut self.buffer[idx + buffer_offset];
-        *b = (*b & !(1 << offset)) | ((value as u16) << offset);
+        self.planes.set_pixel(x, y, value, buffer_offset);
     }
 
     pub fn set_pixel_gray(&mut self, x: usize, y: usize, value: u8) {
-        let sz = self.frame_buffer_len();
+        self.planes.set_pixel_gray(x, y, value);
+    }
+
+    pub fn flush_frame(&mut self) -> Result<(), Error> {
         for k in 0..16 {
-            self.set_pixel(
-                x,
-                y,
-                LUT16[value.min(self.max_brightness) as usize][k] != 0,
-                k * sz,
-            );
+            self.out.flush_plane(self.planes.plane(k))?;
         }
+        Ok(())
+    }
+
+    /// Display one frame, shifting out each run of identical consecutive
+    /// planes once and keeping it lit for the whole run.
+    ///
+    /// Binary content only needs a single transfer per frame this way.
+    /// Outputs that cannot hold a plane get it flushed once per plane instead.
+    ///
+    /// Runs are held by busy waiting, so other tasks cannot stretch one
+    /// plane and shift the gray levels. Only a frame made of a single run,
+    /// where every plane looks the same, yields while it is held.
+    pub async fn show_frame(&mut self) -> Result<(), Error> {
+        let holds = self.out.holds_plane();
+        let mut runs = [(0u8, 0u8); 16];
+        let count = {
+            let r = self.planes.plane_runs();
+            runs[..r.len()].copy_from_slice(r);
+            r.len()
+        };
+        for &(plane, len) in &runs[..count] {
+            let plane = self.planes.plane(plane as usize);
+            if holds {
+                let start = Instant::now();
+                self.out.flush_plane(plane)?;
+                self.plane_time = start.elapsed();
+                if len > 1 {
+                    let hold = self.plane_time * (len as u32 - 1);
+                    if count == 1 {
+                        Timer::after(hold).await;
+                    } else {
+                        block_for(hold);
+                    }
+                }
+            } else {
+                for _ in 0..len {
+                    self.out.flush_plane(plane)?;
+                }
+            }
+        }
+        Ok(())
     }
 
-    pub async fn task_impl(mut self, receiver: ICN2037Receiver) -> Result<(), Error> {
+    pub fn power(&mut self, on: bool) -> Result<(), Error> {
+        self.powered = on;
+        if !on {
+            // keep outputs disabled until powered on again
+            self.out.blank()?;
+        }
+        Ok(())
+    }
+
+    async fn handle_control(
+        &mut self,
+        ctrl: ICN2037Control,
+        receiver: &ICN2037Receiver,
+    ) -> Result<(), Error> {
+        match ctrl {
+            ICN2037Control::SetBrightness(brightness) => {
+                info!("set brightness max {}", brightness);
+                self.planes.set_brightness(brightness)
+            }
+            ICN2037Control::Clear => {
+                // drop the pixels queued before the clear, up to its marker
+                while let Ok(msg) = receiver.try_receive() {
+                    if let ICN2037Message::Clear = msg {
+                        break;
+                    }
+                }
+                self.clear()
+            }
+            ICN2037Control::ClearAll => {
+                while receiver.try_receive().is_ok() {}
+                self.clear()
+            }
+            ICN2037Control::Present => {
+                if self.powered {
+                    self.show_frame().await?;
+                }
+            }
+            ICN2037Control::Power(on) => self.power(on)?,
+            ICN2037Control::MaskPixel((x, y, masked)) => self.planes.mask_pixel(x, y, masked),
+        }
+        Ok(())
+    }
+
+    pub async fn task_impl(
+        mut self,
+        receiver: ICN2037Receiver,
+        control: ICN2037ControlReceiver,
+    ) -> Result<(), Error> {
         let mut msg_count = 0;
         loop {
-            let msg = receiver.try_receive();
-            match msg {
+            // control messages always go before pixel traffic
+            if let Ok(ctrl) = control.try_receive() {
+                self.handle_control(ctrl, &receiver).await?;
+                continue;
+            }
+            match receiver.try_receive() {
                 Ok(msg) => {
                     msg_count += 1;
-                    match msg {
-                        ICN2037Message::SetPixel((x, y, v)) => self.set_pixel_gray(x, y, v),
-                        ICN2037Message::FillPixels((sx, sy, ex, ey, v)) => {
-                            for x in sx..ex {
-                                for y in sy..ey {
-                                    self.set_pixel_gray(x, y, v);
-                                }
-                            }
-                        }
-                        ICN2037Message::Clear => self.buffer.iter_mut().for_each(|x| *x = 0),
-                        ICN2037Message::Buffer(b) => {
-                            // copy buffers
-                            let len = b.len().min(self.buffer.len());
-                            unsafe {
-                                core::ptr::copy_nonoverlapping(
-                                    b.as_ptr(),
-                                    self.buffer.as_mut_ptr(),
-                                    len,
-                                );
-                            }
-                        }
-                        ICN2037Message::Pixels(pixels) => {
-                            let ex = self.config.width.min(pixels.len());
-                            let ey = self.config.height.min(pixels[0].len());
-                            for x in 0..ex {
-                                for y in 0..ey {
-                                    self.set_pixel_gray(x, y, pixels[x][y]);
-                                }
-                            }
-                        }
-                        ICN2037Message::PixelsFrame(frame) => {
-                            for x in 0..25 {
-                                for y in 0..16 {
-                                    self.set_pixel_gray(x, y, frame[x][y]);
-                                }
-                            }
-                        }
-                        ICN2037Message::SetBrightness(brightness) => {
-                            defmt::info!("set brightness max {}", brightness);
-                            self.max_brightness = brightness
-                        }
-                        ICN2037Message::Fullfill(brightness) => {
-                            for x in 0..self.config.width {
-                                for y in 0..self.config.height {
-                                    self.set_pixel_gray(x, y, brightness);
-                                }
-                            }
-                        }
-                    }
+                    self.planes.handle_message(msg);
                 }
                 Err(_) => {
                     if msg_count > 0 {
-                        defmt::debug!("last msg count {}", msg_count);
+                        debug!("last msg count {}", msg_count);
                     }
                     msg_count = 0;
-                    // normal display for one frame
-                    let frame_sz = self.frame_buffer_len();
-                    for k in 0..16 {
-                        self.flush(k * frame_sz)?;
+                    if self.powered {
+                        // normal display for one frame
+                        self.show_frame().await?;
+                        Timer::after_ticks(0).await;
+                    } else {
+                        Timer::after_millis(1).await;
                     }
-                    Timer::after_ticks(0).await;
                 }
             }
         }
     }
 }
 
-impl<'d, SPI, OE, LE> embedded_graphics_core::geometry::OriginDimensions
-    for ICN2037<'d, SPI, OE, LE>
-{
+impl<'d, O> embedded_graphics_core::geometry::OriginDimensions for LedDisplay<'d, O> {
     fn size(&self) -> embedded_graphics_core::prelude::Size {
         embedded_graphics_core::prelude::Size::new(
-            self.config.width as u32,
-            self.config.height as u32,
+            self.planes.config.width as u32,
+            self.planes.config.height as u32,
         )
     }
 }
 
-impl<'d, SPI, OE, LE> embedded_graphics_core::draw_target::DrawTarget for ICN2037<'d, SPI, OE, LE>
+impl<'d, O> embedded_graphics_core::draw_target::DrawTarget for LedDisplay<'d, O>
 where
-    SPI: SpiBus,
-    OE: OutputPin,
-    LE: OutputPin,
+    O: PlaneOutput,
 {
     type Color = embedded_graphics_core::pixelcolor::BinaryColor;
 
@@ -230,11 +304,36 @@ where
 }
 
 pub const BUFFER_SZ: usize = 1024;
+pub const CONTROL_BUFFER_SZ: usize = 8;
 pub type ICN2037Receiver = Receiver<'static, NoopRawMutex, ICN2037Message, BUFFER_SZ>;
+pub type ICN2037ControlReceiver =
+    Receiver<'static, NoopRawMutex, ICN2037Control, CONTROL_BUFFER_SZ>;
+pub type ICN2037ControlSender = Sender<'static, NoopRawMutex, ICN2037Control, CONTROL_BUFFER_SZ>;
 #[derive(Clone)]
 pub struct ICN2037Sender {
     pub config: DisplayConfig,
     pub sender: Sender<'static, NoopRawMutex, ICN2037Message, BUFFER_SZ>,
+    /// High-priority path, checked by the daemon before any pixel traffic.
+    pub control: ICN2037ControlSender,
+}
+
+impl ICN2037Sender {
+    /// Clear the frame ahead of the queued pixel traffic. Pixel messages
+    /// sent before this are dropped, the ones sent after it still land.
+    ///
+    /// With the pixel queue too full for the marker, everything queued is
+    /// dropped instead. Fails only when the control queue is full as well.
+    pub fn try_clear(&self) -> Result<(), Error> {
+        if self.sender.try_send(ICN2037Message::Clear).is_err() {
+            return self
+                .control
+                .try_send(ICN2037Control::ClearAll)
+                .map_err(|_| Error::BufferError);
+        }
+        // without the priority path the marker still clears, in order
+        let _ = self.control.try_send(ICN2037Control::Clear);
+        Ok(())
+    }
 }
 
 impl embedded_graphics_core::geometry::OriginDimensions for ICN2037Sender {
@@ -267,7 +366,7 @@ impl embedded_graphics_core::draw_target::DrawTarget for ICN2037Sender {
                 Ok(_) => {}
                 Err(e) => match e {
                     embassy_sync::channel::TrySendError::Full(_) => {
-                        defmt::warn!("full buffer! {}", e);
+                        warn!("full buffer! {:?}", e);
                     }
                 },
             }
@@ -286,53 +385,41 @@ impl embedded_graphics_core::draw_target::DrawTarget for ICN2037Sender {
             area.bottom_right().unwrap().y as usize,
         );
         let msg = ICN2037Message::FillPixels((sx, sy, ex, ey, color.into_storage()));
-        self.sender.try_send(msg).unwrap();
-        Ok(())
+        self.sender.try_send(msg).map_err(|_| Error::BufferError)
     }
 
     fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
         if color.into_storage() == 0 {
-            self.sender.try_send(ICN2037Message::Clear).unwrap();
-            Ok(())
+            self.try_clear()
         } else {
             self.fill_solid(&self.bounding_box(), color)
         }
     }
 }
 
-pub trait ICN2037Device {
-    fn task(self, receiver: ICN2037Receiver) -> impl Future<Output = Result<(), Error>>;
+/// Anything that can run as the display daemon behind an `ICN2037Sender`.
+pub trait LedDevice {
+    fn task(
+        self,
+        receiver: ICN2037Receiver,
+        control: ICN2037ControlReceiver,
+    ) -> impl Future<Output = Result<(), Error>>;
 }
-impl<'d, SPI, OE, LE> ICN2037Device for ICN2037<'d, SPI, OE, LE>
+pub use LedDevice as ICN2037Device;
+
+impl<'d, O> LedDevice for LedDisplay<'d, O>
 where
-    SPI: SpiBus,
-    OE: OutputPin,
-    LE: OutputPin,
+    O: PlaneOutput,
 {
-    fn task(self, receiver: ICN2037Receiver) -> impl Future<Output = Result<(), Error>> {
-        self.task_impl(receiver)
+    fn task(
+        self,
+        receiver: ICN2037Receiver,
+        control: ICN2037ControlReceiver,
+    ) -> impl Future<Output = Result<(), Error>> {
+        self.task_impl(receiver, control)
     }
 }
 
-const LUT16: [[u8; 16]; 16] = [
-    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], // 0
-    [1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], // 1
-    [1, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0], // 2
-    [1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0], // 3
-    [1, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0], // 4
-    [1, 0, 0, 1, 0, 0, 0, 1, 0, 0, 1, 0, 0, 1, 0, 0], // 5
-    [1, 0, 0, 1, 0, 0, 1, 0, 0, 1, 0, 0, 1, 0, 1, 0], // 6
-    [1, 0, 1, 0, 1, 0, 0, 1, 0, 0, 1, 0, 1, 0, 1, 0], // 7
-    [1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0], // 8
-    [1, 1, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0], // 9
-    [1, 1, 1, 0, 1, 0, 1, 0, 1, 1, 1, 0, 1, 0, 1, 0], // 10
-    [1, 1, 1, 0, 1, 1, 1, 0, 1, 1, 1, 0, 1, 0, 1, 0], // 11
-    [1, 1, 1, 0, 1, 1, 1, 0, 1, 1, 1, 0, 1, 1, 1, 0], // 12
-    [1, 1, 1, 0, 1, 1, 1, 1, 1, 1, 1, 0, 1, 1, 1, 1], // 13
-    [1, 1, 1, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1], // 14
-    [1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1], // 15
-];
-
 #[derive(Debug, Clone, Copy)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum ICN2037Message {
@@ -341,7 +428,27 @@ pub enum ICN2037Message {
     Buffer(&'static [u16]),
     Pixels(&'static [&'static [u8]]),
     PixelsFrame(&'static [[u8; 16]; 25]),
-    Clear,
     Fullfill(u8),
+    /// Clear the frame in order with the pixel messages around it, marks
+    /// where an `ICN2037Control::Clear` stops dropping them.
+    Clear,
+}
+
+/// Commands that must not wait behind bulk pixel traffic.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ICN2037Control {
     SetBrightness(u8),
+    /// Clear the frame, dropping the pixel messages queued up to the next
+    /// `ICN2037Message::Clear`. Sent by `ICN2037Sender::try_clear`.
+    Clear,
+    /// Clear the frame and drop every queued pixel message, for when the
+    /// pixel queue had no room for the `ICN2037Message::Clear` marker.
+    ClearAll,
+    /// Flush one frame right away, even while pixel messages are pending.
+    Present,
+    /// Blank the outputs and stop refreshing while off.
+    Power(bool),
+    /// Mark a dead or stuck pixel, masked pixels are kept off.
+    MaskPixel((usize, usize, bool)),
 }
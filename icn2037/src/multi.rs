@@ -0,0 +1,149 @@
+use core::future::Future;
+
+use embassy_futures::block_on;
+use embassy_futures::join::join_array;
+use embassy_time::{block_for, Duration};
+
+use crate::{Bitplanes, DisplayConfig, Error, LedDisplay, PixelAddr, PlaneOutput};
+
+/// Pin level access to one chain of a `MultiChain`.
+///
+/// Chains sitting on different SPI peripherals (and thus of different
+/// types) can be grouped behind an enum implementing this.
+pub trait ChainPins {
+    /// Shift `words` into the chain without latching them. The transfer
+    /// should run on its own, e.g. by DMA, so that all chains shift at once.
+    fn write_words(&mut self, words: &[u16]) -> impl Future<Output = Result<(), Error>>;
+    fn set_latch(&mut self, high: bool) -> Result<(), Error>;
+    fn set_output_enable(&mut self, enabled: bool) -> Result<(), Error>;
+    /// Whether the chip wants a latch pulse after every word.
+    fn latch_per_word(&self) -> bool {
+        true
+    }
+    /// How long the latch input is held high.
+    fn latch_pulse(&self) -> Duration {
+        Duration::from_ticks(0)
+    }
+    /// How long outputs stay off between two planes.
+    fn oe_blanking(&self) -> Duration {
+        Duration::from_ticks(0)
+    }
+}
+
+/// `N` chains refreshed as one display.
+///
+/// Every plane is split into `N` equal shares, chain `i` shows the `i`-th
+/// share, so `DisplayConfig::map_pixel` decides which chain a pixel lives
+/// on. The shares are shifted into all chains concurrently, and latch and
+/// `OE` pulses are issued on all chains together once every transfer is
+/// done, so they always show the same plane of the same frame. All chains
+/// are expected to use the same chip, the first one decides when and how
+/// long to latch and blank.
+pub struct MultiChain<P, const N: usize> {
+    chains: [P; N],
+}
+
+impl<P, const N: usize> MultiChain<P, N>
+where
+    P: ChainPins,
+{
+    pub fn new(chains: [P; N]) -> Self {
+        assert!(N > 0);
+        Self { chains }
+    }
+
+    pub fn into_chains(self) -> [P; N] {
+        self.chains
+    }
+
+    /// Shift `share(i)` into chain `i`, on all chains at once.
+    fn write_all<'p>(&mut self, share: impl Fn(usize) -> &'p [u16]) -> Result<(), Error> {
+        let mut i = 0;
+        let writes = self.chains.each_mut().map(|chain| {
+            let words = share(i);
+            i += 1;
+            chain.write_words(words)
+        });
+        // the plane must be out before it is latched, so wait right here
+        block_on(join_array(writes)).into_iter().collect()
+    }
+
+    fn set_latch_all(&mut self, high: bool) -> Result<(), Error> {
+        for chain in self.chains.iter_mut() {
+            chain.set_latch(high)?;
+        }
+        Ok(())
+    }
+
+    fn latch_all(&mut self) -> Result<(), Error> {
+        let pulse = self.chains[0].latch_pulse();
+        self.set_latch_all(true)?;
+        if pulse.as_ticks() > 0 {
+            block_for(pulse);
+        }
+        self.set_latch_all(false)
+    }
+
+    fn output_enable_all(&mut self, enabled: bool) -> Result<(), Error> {
+        for chain in self.chains.iter_mut() {
+            chain.set_output_enable(enabled)?;
+        }
+        Ok(())
+    }
+}
+
+impl<P, const N: usize> PlaneOutput for MultiChain<P, N>
+where
+    P: ChainPins,
+{
+    fn start(&mut self) -> Result<(), Error> {
+        self.output_enable_all(false)?;
+        self.set_latch_all(false)
+    }
+
+    fn flush_plane(&mut self, plane: &[u16]) -> Result<(), Error> {
+        assert!(
+            plane.len().is_multiple_of(N),
+            "plane does not split evenly over the chains"
+        );
+        let words = plane.len() / N;
+        let latch_per_word = self.chains[0].latch_per_word();
+        if latch_per_word {
+            for i in 0..words {
+                self.write_all(|c| &plane[c * words + i..c * words + i + 1])?;
+                self.latch_all()?;
+            }
+        } else {
+            self.write_all(|c| &plane[c * words..(c + 1) * words])?;
+        }
+        self.output_enable_all(false)?;
+        if !latch_per_word {
+            self.latch_all()?;
+        }
+        let blanking = self.chains[0].oe_blanking();
+        if blanking.as_ticks() > 0 {
+            block_for(blanking);
+        }
+        self.output_enable_all(true)
+    }
+
+    fn blank(&mut self) -> Result<(), Error> {
+        self.output_enable_all(false)
+    }
+}
+
+pub type MultiICN2037<'d, P, const N: usize> = LedDisplay<'d, MultiChain<P, N>>;
+
+impl<'d, P, const N: usize> LedDisplay<'d, MultiChain<P, N>>
+where
+    P: ChainPins,
+{
+    pub fn new(
+        chains: [P; N],
+        config: DisplayConfig,
+        buffer: &'d mut [u16],
+        lut: &'d mut [PixelAddr],
+    ) -> Self {
+        Self::with_output(MultiChain::new(chains), Bitplanes::new(config, buffer, lut))
+    }
+}
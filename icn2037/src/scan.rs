@@ -0,0 +1,172 @@
+use core::marker::PhantomData;
+
+use embassy_time::{block_for, Duration, Instant};
+use embedded_hal::digital::OutputPin;
+use embedded_hal::spi::SpiBus;
+
+use crate::chip::{Icn2037, LedChip};
+use crate::{Bitplanes, DisplayConfig, Error, LedDisplay, PixelAddr, PlaneOutput};
+
+#[derive(Debug, Clone)]
+pub struct ScanConfig {
+    /// Number of multiplexed lines, 4 for a 1/4 scan panel.
+    pub scan_lines: usize,
+    /// Time the outputs stay blank after switching lines, against ghosting.
+    pub blanking: Duration,
+}
+impl ScanConfig {
+    pub fn new(scan_lines: usize, blanking: Duration) -> Self {
+        Self {
+            scan_lines,
+            blanking,
+        }
+    }
+}
+
+/// A chain of `C` chips, ICN2037 by default, driving a row-scanned panel
+/// through address pins.
+///
+/// Every plane holds all lines back to back, `plane.len() / scan_lines`
+/// words per line, so `DisplayConfig::map_pixel` decides which line a pixel
+/// is shown on.
+pub struct ScanChain<'d, SPI, OE, LE, A, C = Icn2037> {
+    spi: SPI,
+    oe: OE,
+    le: LE,
+    addr: &'d mut [A],
+    scan: ScanConfig,
+    chip: PhantomData<C>,
+}
+
+impl<'d, SPI, OE, LE, A, C> ScanChain<'d, SPI, OE, LE, A, C>
+where
+    SPI: SpiBus,
+    OE: OutputPin,
+    LE: OutputPin,
+    A: OutputPin,
+    C: LedChip,
+{
+    pub fn new(spi: SPI, oe: OE, le: LE, addr: &'d mut [A], scan: ScanConfig) -> Self {
+        assert!(scan.scan_lines > 0 && scan.scan_lines <= 1 << addr.len());
+        Self {
+            spi,
+            oe,
+            le,
+            addr,
+            scan,
+            chip: PhantomData,
+        }
+    }
+
+    fn set_output_enable(&mut self, enabled: bool) -> Result<(), Error> {
+        if enabled == C::OE_ACTIVE_LOW {
+            self.oe.set_low().map_err(|_| Error::PinError)
+        } else {
+            self.oe.set_high().map_err(|_| Error::PinError)
+        }
+    }
+
+    fn latch(&mut self) -> Result<(), Error> {
+        self.le.set_high().map_err(|_| Error::PinError)?;
+        if C::LATCH_PULSE.as_ticks() > 0 {
+            block_for(C::LATCH_PULSE);
+        }
+        self.le.set_low().map_err(|_| Error::PinError)
+    }
+
+    fn select_line(&mut self, line: usize) -> Result<(), Error> {
+        for (i, pin) in self.addr.iter_mut().enumerate() {
+            if line & (1 << i) != 0 {
+                pin.set_high().map_err(|_| Error::PinError)?;
+            } else {
+                pin.set_low().map_err(|_| Error::PinError)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'d, SPI, OE, LE, A, C> PlaneOutput for ScanChain<'d, SPI, OE, LE, A, C>
+where
+    SPI: SpiBus,
+    OE: OutputPin,
+    LE: OutputPin,
+    A: OutputPin,
+    C: LedChip,
+{
+    fn start(&mut self) -> Result<(), Error> {
+        self.set_output_enable(false)?;
+        self.le.set_low().map_err(|_| Error::PinError)?;
+        self.select_line(0)
+    }
+
+    fn flush_plane(&mut self, plane: &[u16]) -> Result<(), Error> {
+        let words = plane.len() / self.scan.scan_lines;
+        let blanking = self.scan.blanking.max(C::OE_BLANKING);
+        let mut shift_time = Duration::from_ticks(0);
+        for line in 0..self.scan.scan_lines {
+            let start = Instant::now();
+            if C::LATCH_PER_WORD {
+                // every latch shows at once, so the line goes in dark
+                self.set_output_enable(false)?;
+            }
+            // otherwise shift the next line in while the previous one is lit
+            for w in &plane[line * words..(line + 1) * words] {
+                self.spi
+                    .write(&w.to_be_bytes())
+                    .map_err(|_| Error::BusError)?;
+                if C::LATCH_PER_WORD {
+                    self.latch()?;
+                }
+            }
+            shift_time = start.elapsed();
+            self.set_output_enable(false)?;
+            if !C::LATCH_PER_WORD {
+                self.latch()?;
+            }
+            self.select_line(line)?;
+            block_for(blanking);
+            self.set_output_enable(true)?;
+        }
+        // the last line is lit as long as the others were while the next
+        // one went in, not until whenever the next plane comes
+        block_for(shift_time);
+        self.set_output_enable(false)
+    }
+
+    fn blank(&mut self) -> Result<(), Error> {
+        self.set_output_enable(false)
+    }
+
+    fn holds_plane(&self) -> bool {
+        false
+    }
+}
+
+pub type ScanICN2037<'d, SPI, OE, LE, A> = LedDisplay<'d, ScanChain<'d, SPI, OE, LE, A, Icn2037>>;
+
+impl<'d, SPI, OE, LE, A, C> LedDisplay<'d, ScanChain<'d, SPI, OE, LE, A, C>>
+where
+    SPI: SpiBus,
+    OE: OutputPin,
+    LE: OutputPin,
+    A: OutputPin,
+    C: LedChip,
+{
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        spi: SPI,
+        oe: OE,
+        le: LE,
+        addr: &'d mut [A],
+        scan: ScanConfig,
+        config: DisplayConfig,
+        buffer: &'d mut [u16],
+        lut: &'d mut [PixelAddr],
+    ) -> Self {
+        Self::with_output(
+            ScanChain::new(spi, oe, le, addr, scan),
+            Bitplanes::new(config, buffer, lut),
+        )
+    }
+}
@@ -0,0 +1,235 @@
+//! Runs the display daemon on the host, with
+//! `cargo test -p icn2037 --no-default-features --features std,log --target <host>`,
+//! the host target overriding the one from `.cargo/config.toml`.
+#![cfg(feature = "std")]
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use embassy_futures::block_on;
+use embassy_futures::select::select;
+use embassy_sync::channel::Channel;
+use embassy_time::Timer;
+use icn2037::{
+    Bitplanes, ChainPins, DisplayConfig, Error, ICN2037Control, ICN2037ControlReceiver,
+    ICN2037Message, ICN2037Receiver, ICN2037Sender, LedDisplay, MultiChain, PixelAddr, PlaneOutput,
+};
+
+const W: usize = 8;
+const H: usize = 4;
+const WORDS: usize = W * H / 16;
+
+/// Keeps the last plane it was given.
+struct Recorder(Rc<RefCell<Vec<u16>>>);
+
+impl PlaneOutput for Recorder {
+    fn start(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn flush_plane(&mut self, plane: &[u16]) -> Result<(), Error> {
+        *self.0.borrow_mut() = plane.to_vec();
+        Ok(())
+    }
+
+    fn blank(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+fn map_pixel(_: &DisplayConfig, x: usize, y: usize) -> (usize, usize) {
+    let i = x * H + y;
+    (i / 16, i % 16)
+}
+
+struct Setup {
+    display: LedDisplay<'static, Recorder>,
+    sender: ICN2037Sender,
+    receiver: ICN2037Receiver,
+    control: ICN2037ControlReceiver,
+    shown: Rc<RefCell<Vec<u16>>>,
+}
+
+fn setup() -> Setup {
+    let pixels = Box::leak(Box::new(Channel::new()));
+    let control = Box::leak(Box::new(Channel::new()));
+    let config = DisplayConfig::new(W, H, map_pixel);
+    let buffer = Box::leak(vec![0u16; WORDS * 16].into_boxed_slice());
+    let lut = Box::leak(vec![PixelAddr::default(); W * H].into_boxed_slice());
+    let shown = Rc::new(RefCell::new(Vec::new()));
+    let display = LedDisplay::with_output(
+        Recorder(shown.clone()),
+        Bitplanes::new(config.clone(), buffer, lut),
+    );
+    Setup {
+        display,
+        sender: ICN2037Sender {
+            config,
+            sender: pixels.sender(),
+            control: control.sender(),
+        },
+        receiver: pixels.receiver(),
+        control: control.receiver(),
+        shown,
+    }
+}
+
+/// Let the daemon work through its queues, then return the plane on the
+/// LEDs. Only full brightness pixels are used, so that is the whole image.
+fn run(setup: Setup) -> Vec<u16> {
+    let Setup {
+        display,
+        receiver,
+        control,
+        shown,
+        ..
+    } = setup;
+    block_on(select(
+        display.task_impl(receiver, control),
+        Timer::after_millis(50),
+    ));
+    let plane = shown.borrow().clone();
+    plane
+}
+
+fn bit(x: usize, y: usize) -> Vec<u16> {
+    let mut plane = vec![0; WORDS];
+    let (idx, offset) = map_pixel(&DisplayConfig::new(W, H, map_pixel), x, y);
+    plane[idx] |= 1 << offset;
+    plane
+}
+
+#[test]
+fn pixels_reach_the_output() {
+    let s = setup();
+    s.sender
+        .sender
+        .try_send(ICN2037Message::SetPixel((3, 2, 15)))
+        .unwrap();
+    assert_eq!(run(s), bit(3, 2));
+}
+
+#[test]
+fn clear_drops_only_earlier_pixels() {
+    let s = setup();
+    s.sender
+        .sender
+        .try_send(ICN2037Message::SetPixel((0, 0, 15)))
+        .unwrap();
+    s.sender.try_clear().unwrap();
+    s.sender
+        .sender
+        .try_send(ICN2037Message::SetPixel((7, 3, 15)))
+        .unwrap();
+    assert_eq!(run(s), bit(7, 3));
+}
+
+#[test]
+fn clear_gets_through_a_full_queue() {
+    let s = setup();
+    while s
+        .sender
+        .sender
+        .try_send(ICN2037Message::SetPixel((2, 1, 15)))
+        .is_ok()
+    {}
+    s.sender.try_clear().unwrap();
+    assert_eq!(run(s), vec![0; WORDS]);
+}
+
+#[test]
+fn control_goes_before_pixels() {
+    let s = setup();
+    s.sender
+        .sender
+        .try_send(ICN2037Message::SetPixel((1, 1, 15)))
+        .unwrap();
+    // queued after the pixel, applied before it
+    s.sender
+        .control
+        .try_send(ICN2037Control::SetBrightness(0))
+        .unwrap();
+    assert_eq!(run(s), vec![0; WORDS]);
+}
+
+/// A chain that keeps what was shifted into it and latched.
+#[derive(Default)]
+struct MockChain {
+    shifted: Vec<u16>,
+    latched: Vec<u16>,
+    latch: bool,
+}
+
+impl ChainPins for MockChain {
+    async fn write_words(&mut self, words: &[u16]) -> Result<(), Error> {
+        self.shifted.extend_from_slice(words);
+        Ok(())
+    }
+
+    fn set_latch(&mut self, high: bool) -> Result<(), Error> {
+        if high && !self.latch {
+            self.latched = core::mem::take(&mut self.shifted);
+        }
+        self.latch = high;
+        Ok(())
+    }
+
+    fn set_output_enable(&mut self, _enabled: bool) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn latch_per_word(&self) -> bool {
+        false
+    }
+}
+
+#[test]
+fn multi_chain_splits_planes() {
+    let mut multi = MultiChain::new([MockChain::default(), MockChain::default()]);
+    multi.flush_plane(&[1, 2, 3, 4]).unwrap();
+    let [a, b] = multi.into_chains();
+    assert_eq!(a.latched, [1, 2]);
+    assert_eq!(b.latched, [3, 4]);
+}
+
+#[test]
+#[should_panic]
+fn multi_chain_rejects_uneven_planes() {
+    let mut multi = MultiChain::new([MockChain::default(), MockChain::default()]);
+    let _ = multi.flush_plane(&[1, 2, 3]);
+}
+
+#[test]
+fn raw_buffers_keep_masked_pixels_off() {
+    let mut s = setup();
+    s.display
+        .planes
+        .set_pixel_mask(Box::leak(Box::new([0u32; 1])));
+    s.display.planes.mask_pixel(3, 2, true);
+    let lit = Box::leak(vec![u16::MAX; WORDS * 16].into_boxed_slice());
+    s.sender
+        .sender
+        .try_send(ICN2037Message::Buffer(lit))
+        .unwrap();
+    let mut expected = vec![u16::MAX; WORDS];
+    let (idx, offset) = map_pixel(&DisplayConfig::new(W, H, map_pixel), 3, 2);
+    expected[idx] &= !(1 << offset);
+    assert_eq!(run(s), expected);
+}
+
+#[test]
+fn preloaded_mask_is_kept() {
+    let mut s = setup();
+    let (idx, offset) = map_pixel(&DisplayConfig::new(W, H, map_pixel), 3, 2);
+    s.display
+        .planes
+        .set_pixel_mask(Box::leak(Box::new([1u32 << (3 * H + 2)])));
+    assert!(s.display.planes.is_masked(3, 2));
+    s.sender
+        .sender
+        .try_send(ICN2037Message::Fullfill(15))
+        .unwrap();
+    let mut expected = vec![u16::MAX; WORDS];
+    expected[idx] &= !(1 << offset);
+    assert_eq!(run(s), expected);
+}
@@ -0,0 +1,319 @@
+#![allow(dead_code)]
+
+use crate::lifegame::{BoarderPolicy, Neighbour};
+use crate::rule::Rule;
+
+/// Bits of the dying age, enough for `Rule::MAX_STATES`.
+const AGE_BITS: usize = 4;
+/// Bits of a neighbour count, enough for a range 3 Moore neighbourhood.
+const COUNT_BITS: usize = 6;
+/// Bits of a heat map age, which stops at 15.
+const HEAT_BITS: usize = 4;
+
+/// Bit-packed live cells, one `u32` per column with bit `y` holding row `y`.
+///
+/// Stepping works on whole columns: the shifted neighbour columns are summed
+/// by a bit-sliced adder, so every row of a column is counted at once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Board<const W: usize, const H: usize> {
+    alive: [u32; W],
+}
+
+/// Age of the dying cells of Generations rules, bit-sliced over `Board`
+/// columns, 0 when not dying.
+///
+/// Only one is kept beside the two boards: a cell's next age depends on
+/// nothing but its own state, so `Board::step` ages it in place, and
+/// two-state rules leave it empty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ages<const W: usize> {
+    planes: [[u32; W]; AGE_BITS],
+}
+
+/// Generations since each cell last turned alive or dead, for the heat map,
+/// bit-sliced over `Board` columns like `Ages` and stopping at 15.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Heat<const W: usize> {
+    planes: [[u32; W]; HEAT_BITS],
+}
+
+impl<const W: usize, const H: usize> Default for Board<W, H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const W: usize> Default for Ages<W> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const W: usize> Ages<W> {
+    pub const fn new() -> Self {
+        Self {
+            planes: [[0; W]; AGE_BITS],
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> u8 {
+        (0..AGE_BITS).fold(0, |a, i| a | (((self.planes[i][x] >> y) & 1) as u8) << i)
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, age: u8) {
+        for i in 0..AGE_BITS {
+            set_bits(&mut self.planes[i][x], 1 << y, age & (1 << i) != 0);
+        }
+    }
+
+    /// Dying cells of column `x`, bit `y` for row `y`.
+    pub fn column(&self, x: usize) -> u32 {
+        self.planes.iter().fold(0, |d, plane| d | plane[x])
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.planes.iter().flatten().all(|c| *c == 0)
+    }
+}
+
+impl<const W: usize> Default for Heat<W> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const W: usize> Heat<W> {
+    /// Every cell as old as it gets.
+    pub const fn new() -> Self {
+        Self {
+            planes: [[u32::MAX; W]; HEAT_BITS],
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> u8 {
+        (0..HEAT_BITS).fold(0, |a, i| a | (((self.planes[i][x] >> y) & 1) as u8) << i)
+    }
+
+    /// Start the cell over at age 0.
+    pub fn reset(&mut self, x: usize, y: usize) {
+        for plane in self.planes.iter_mut() {
+            plane[x] &= !(1 << y);
+        }
+    }
+
+    /// Age every cell by a generation, except those that changed from
+    /// `last` to `next`, which start over.
+    pub fn step<const H: usize>(&mut self, last: &Board<W, H>, next: &Board<W, H>) {
+        for x in 0..W {
+            let mut age = self.planes.map(|plane| plane[x]);
+            let changed = last.alive[x] ^ next.alive[x];
+            let old = equals(&age, (1 << HEAT_BITS) - 1);
+            add_bit(&mut age, !old);
+            for (plane, a) in self.planes.iter_mut().zip(age) {
+                plane[x] = a & !changed;
+            }
+        }
+    }
+}
+
+impl<const W: usize, const H: usize> Board<W, H> {
+    const ROWS: u32 = if H >= 32 { u32::MAX } else { (1 << H) - 1 };
+
+    pub const fn new() -> Self {
+        Self { alive: [0; W] }
+    }
+
+    pub fn is_alive(&self, x: usize, y: usize) -> bool {
+        self.alive[x] & (1 << y) != 0
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, alive: bool) {
+        set_bits(&mut self.alive[x], 1 << y, alive);
+    }
+
+    /// Live cells of column `x`, bit `y` for row `y`.
+    pub fn column(&self, x: usize) -> u32 {
+        self.alive[x]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.alive.iter().all(|c| *c == 0)
+    }
+
+    pub fn population(&self) -> u32 {
+        self.alive.iter().map(|c| c.count_ones()).sum()
+    }
+
+    /// Cells born and cells that stopped living on the way to `next`.
+    pub fn changes(&self, next: &Self) -> (u32, u32) {
+        self.alive
+            .iter()
+            .zip(next.alive.iter())
+            .fold((0, 0), |(b, d), (a, n)| {
+                (b + (n & !a).count_ones(), d + (a & !n).count_ones())
+            })
+    }
+
+    /// Hash of every cell state, dying ones taken from `ages`. With
+    /// `translate` the board is taken as a torus and all its translations
+    /// hash the same, so gliders going round it are caught as cycles too.
+    pub fn hash(&self, ages: &Ages<W>, translate: bool) -> u32 {
+        let (shifts, starts) = if translate { (H as u32, W) } else { (1, 1) };
+        let mut columns = [0u32; W];
+        let mut best = u32::MAX;
+        for dy in 0..shifts {
+            for (x, c) in columns.iter_mut().enumerate() {
+                *c = ages
+                    .planes
+                    .iter()
+                    .fold(mix(0, Self::rotate(self.alive[x], dy)), |h, plane| {
+                        mix(h, Self::rotate(plane[x], dy))
+                    });
+            }
+            for dx in 0..starts {
+                best = best.min((0..W).fold(0, |h, x| mix(h, columns[(x + dx) % W])));
+            }
+        }
+        best
+    }
+
+    /// Column rotated within its `H` rows, so bit `y` holds row `y + dy`.
+    fn rotate(col: u32, dy: u32) -> u32 {
+        if dy == 0 {
+            col
+        } else {
+            (col >> dy | col << (H as u32 - dy)) & Self::ROWS
+        }
+    }
+
+    /// Live cells of column `x` moved up by `dy`, so bit `y` holds row
+    /// `y + dy`, with cells off the board handled by `policy`.
+    fn shifted(&self, x: i32, dy: i32, policy: &BoarderPolicy) -> u32 {
+        let on_board = x >= 0 && x < W as i32;
+        let s = match policy {
+            BoarderPolicy::Ignored if !on_board => return 0,
+            BoarderPolicy::Ignored if dy >= 0 => self.alive[x as usize] >> dy,
+            BoarderPolicy::Ignored => self.alive[x as usize] << -dy,
+            BoarderPolicy::Looping => {
+                let col = self.alive[x.rem_euclid(W as i32) as usize];
+                Self::rotate(col, dy.rem_euclid(H as i32) as u32)
+            }
+            _ => return self.shifted_cells(x, dy, policy),
+        };
+        s & Self::ROWS
+    }
+
+    /// `shifted` for the policies that do not map whole columns, with the
+    /// rows that fall off the board looked up one by one.
+    fn shifted_cells(&self, x: i32, dy: i32, policy: &BoarderPolicy) -> u32 {
+        let (mut s, rows) = if x >= 0 && x < W as i32 {
+            let col = self.alive[x as usize];
+            if dy >= 0 {
+                ((col >> dy) & (Self::ROWS >> dy), H - dy as usize..H)
+            } else {
+                ((col << -dy) & Self::ROWS, 0..-dy as usize)
+            }
+        } else {
+            (0, 0..H)
+        };
+        for y in rows {
+            let alive = match policy.locate(x, y as i32 + dy, W, H) {
+                Neighbour::Cell(cx, cy) => self.alive[cx] & (1 << cy) != 0,
+                Neighbour::Alive => true,
+                Neighbour::Dead => false,
+            };
+            if alive {
+                s |= 1 << y;
+            }
+        }
+        s
+    }
+
+    /// Compute the generation after this one into `next`, keeping the
+    /// `unavailable` cells dead. `ages` goes from this generation's dying
+    /// cells to the next one's.
+    pub fn step(
+        &self,
+        next: &mut Self,
+        ages: &mut Ages<W>,
+        rule: &Rule,
+        policy: &BoarderPolicy,
+        unavailable: &[u32; W],
+    ) {
+        let r = rule.range as i32;
+        for (x, blocked) in unavailable.iter().enumerate() {
+            let mut count = [0u32; COUNT_BITS];
+            for dx in -r..=r {
+                for dy in -r..=r {
+                    if rule.is_neighbour(dx, dy) {
+                        add_bit(&mut count, self.shifted(x as i32 + dx, dy, policy));
+                    }
+                }
+            }
+            let born = matches(&count, rule.birth);
+            let survive = matches(&count, rule.survival);
+            let alive = self.alive[x];
+            let keep = !blocked & Self::ROWS;
+            if rule.states <= 2 {
+                next.alive[x] = (born & !alive | survive & alive) & keep;
+                continue;
+            }
+            let dying = ages.column(x);
+            next.alive[x] = (born & !alive & !dying | survive & alive) & keep;
+
+            // dying cells age by one, cells that stop living start at age 1
+            let mut age = [0u32; AGE_BITS];
+            let mut carry = dying;
+            for (i, a) in age.iter_mut().enumerate() {
+                *a = ages.planes[i][x] ^ carry;
+                carry &= ages.planes[i][x];
+            }
+            age[0] |= alive & !survive;
+            let expired = equals(&age, rule.states as u32 - 1);
+            for (i, a) in age.iter().enumerate() {
+                ages.planes[i][x] = a & !expired & keep;
+            }
+        }
+    }
+}
+
+fn mix(h: u32, word: u32) -> u32 {
+    (h.rotate_left(5) ^ word).wrapping_mul(0x9e37_79b9)
+}
+
+fn set_bits(word: &mut u32, bits: u32, value: bool) {
+    if value {
+        *word |= bits;
+    } else {
+        *word &= !bits;
+    }
+}
+
+/// Add one bit to each of the bit-sliced counters in `count`.
+fn add_bit<const N: usize>(count: &mut [u32; N], mut carry: u32) {
+    for c in count.iter_mut() {
+        if carry == 0 {
+            break;
+        }
+        let t = *c & carry;
+        *c ^= carry;
+        carry = t;
+    }
+}
+
+/// Bits where the bit-sliced counter equals `n`.
+fn equals<const N: usize>(count: &[u32; N], n: u32) -> u32 {
+    count.iter().enumerate().fold(u32::MAX, |r, (i, c)| {
+        r & if n & (1 << i) != 0 { *c } else { !*c }
+    })
+}
+
+/// Bits where the bit-sliced counter is one of the counts set in `mask`.
+fn matches<const N: usize>(count: &[u32; N], mut mask: u64) -> u32 {
+    let mut r = 0;
+    while mask != 0 {
+        r |= equals(count, mask.trailing_zeros());
+        mask &= mask - 1;
+    }
+    r
+}
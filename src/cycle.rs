@@ -0,0 +1,61 @@
+#![allow(dead_code)]
+
+/// Longest period `CycleDetector` can keep hashes for.
+pub const MAX_PERIOD: usize = 64;
+
+/// Ring buffer of recent board hashes, finding a board that comes back
+/// within `max_period` generations.
+pub struct CycleDetector {
+    hashes: [u32; MAX_PERIOD],
+    head: usize,
+    len: usize,
+    max_period: usize,
+    /// Full periods to watch a cycle for before reporting it.
+    max_repeats: u32,
+    period: Option<usize>,
+    /// Generations in a row that matched the one `period` before.
+    matched: u32,
+}
+
+impl CycleDetector {
+    pub fn new(max_period: usize, max_repeats: u32) -> Self {
+        Self {
+            hashes: [0; MAX_PERIOD],
+            head: 0,
+            len: 0,
+            max_period: max_period.clamp(1, MAX_PERIOD),
+            max_repeats: max_repeats.max(1),
+            period: None,
+            matched: 0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.len = 0;
+        self.period = None;
+        self.matched = 0;
+    }
+
+    /// Record the hash of the next generation, returning the period once
+    /// the board has gone around its cycle `max_repeats` times.
+    pub fn push(&mut self, hash: u32) -> Option<usize> {
+        let found = (1..=self.len.min(self.max_period))
+            .find(|p| self.hashes[(self.head + MAX_PERIOD - p) % MAX_PERIOD] == hash);
+        match found {
+            Some(p) if Some(p) == self.period => self.matched += 1,
+            Some(p) => {
+                self.period = Some(p);
+                self.matched = 1;
+            }
+            None => {
+                self.period = None;
+                self.matched = 0;
+            }
+        }
+        self.hashes[self.head] = hash;
+        self.head = (self.head + 1) % MAX_PERIOD;
+        self.len = (self.len + 1).min(MAX_PERIOD);
+        self.period
+            .filter(|p| self.matched >= *p as u32 * self.max_repeats)
+    }
+}
@@ -0,0 +1,80 @@
+/// Soups kept in `HallOfFame`.
+pub const HALL_LEN: usize = 5;
+
+/// A soup and how long it lasted, with what is needed to replay it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Record {
+    pub seed: u32,
+    /// Generations until the board settled into a cycle.
+    pub generations: u32,
+    /// Index into `RULES`.
+    pub rule_idx: u8,
+    /// Index into `BoarderPolicy::ALL`.
+    pub border_idx: u8,
+}
+
+impl Record {
+    fn same_soup(&self, other: &Record) -> bool {
+        self.seed == other.seed
+            && self.rule_idx == other.rule_idx
+            && self.border_idx == other.border_idx
+    }
+}
+
+/// The longest lived soups, longest first.
+#[derive(Debug, Clone, Copy)]
+pub struct HallOfFame {
+    entries: [Record; HALL_LEN],
+    len: usize,
+}
+
+impl Default for HallOfFame {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HallOfFame {
+    pub const fn new() -> Self {
+        Self {
+            entries: [Record {
+                seed: 0,
+                generations: 0,
+                rule_idx: 0,
+                border_idx: 0,
+            }; HALL_LEN],
+            len: 0,
+        }
+    }
+
+    pub fn entries(&self) -> &[Record] {
+        &self.entries[..self.len]
+    }
+
+    /// Put `record` in its place and return its rank, or `None` if it does
+    /// not beat the soups kept. A soup already in the table only moves up,
+    /// so replaying it does not fill the table with copies.
+    pub fn insert(&mut self, record: Record) -> Option<usize> {
+        if let Some(i) = self.entries().iter().position(|r| r.same_soup(&record)) {
+            if record.generations <= self.entries[i].generations {
+                return None;
+            }
+            self.entries.copy_within(i + 1..self.len, i);
+            self.len -= 1;
+        }
+        let rank = self
+            .entries()
+            .iter()
+            .position(|r| record.generations > r.generations)
+            .unwrap_or(self.len);
+        if rank == HALL_LEN {
+            return None;
+        }
+        self.len = (self.len + 1).min(HALL_LEN);
+        // the last one drops out when full
+        self.entries.copy_within(rank..self.len - 1, rank + 1);
+        self.entries[rank] = record;
+        Some(rank)
+    }
+}
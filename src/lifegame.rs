@@ -1,29 +1,158 @@
 #![allow(dead_code)]
 
 use embassy_time::Timer;
-use icn2037::{ICN2037Message, ICN2037Sender};
+use icn2037::{ICN2037Control, ICN2037Message, ICN2037Sender};
 
+use crate::board::{Ages, Board, Heat};
+use crate::cycle::CycleDetector;
+use crate::pattern_format::{Orientation, PatternBuf};
 use crate::patterns::*;
+use crate::rule::Rule;
+use crate::stats::{GenStats, History};
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, PartialOrd)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum CellState {
     #[default]
-    Dead = 0,
-    Alive = 1,
+    Dead,
+    Alive,
+    /// Generations rules only, counts up from 1 until the cell is dead.
+    Dying(u8),
 }
-#[derive(Debug, Default)]
+/// How `LifeGame::stamp` puts a pattern on the board.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct StampMode {
+    /// Carry cells past an edge over to the other side, as on a torus.
+    pub wrap: bool,
+    /// Only add the live cells instead of writing the whole bounding box.
+    pub or: bool,
+    /// Refuse the placement when a live cell is this close to one of the
+    /// pattern's, `None` to allow overlapping.
+    pub margin: Option<u8>,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum StampError {
+    /// Wider or taller than the board.
+    TooLarge,
+    /// Runs past an edge without `wrap`.
+    OutOfBounds,
+    /// Live cells within the margin.
+    Collision,
+}
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum BoarderPolicy {
     // #[default]
     Ignored,
     #[default]
     Looping,
+    /// Left and right edges joined as on a torus, top and bottom joined
+    /// the other way round.
+    Klein,
+    /// Both pairs of edges joined the other way round, a cross-surface.
+    Projective,
+    /// Cells past an edge mirror the ones inside it.
+    Reflecting,
+    /// Cells past an edge count as alive.
+    LiveBorder,
+}
+/// Where `BoarderPolicy::locate` finds a neighbour.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Neighbour {
+    Cell(usize, usize),
+    Dead,
+    Alive,
+}
+impl BoarderPolicy {
+    pub const ALL: [BoarderPolicy; 6] = [
+        BoarderPolicy::Looping,
+        BoarderPolicy::Ignored,
+        BoarderPolicy::Klein,
+        BoarderPolicy::Projective,
+        BoarderPolicy::Reflecting,
+        BoarderPolicy::LiveBorder,
+    ];
+    pub fn name(&self) -> &'static str {
+        match self {
+            BoarderPolicy::Ignored => "DEAD",
+            BoarderPolicy::Looping => "TORUS",
+            BoarderPolicy::Klein => "KLEIN",
+            BoarderPolicy::Projective => "PROJ",
+            BoarderPolicy::Reflecting => "MIRROR",
+            BoarderPolicy::LiveBorder => "LIVE",
+        }
+    }
+    /// Find the neighbour at (`x`, `y`), which may be a few cells off a
+    /// `w` by `h` board.
+    pub fn locate(&self, x: i32, y: i32, w: usize, h: usize) -> Neighbour {
+        let (w, h) = (w as i32, h as i32);
+        let off_x = x < 0 || x >= w;
+        let off_y = y < 0 || y >= h;
+        if !off_x && !off_y {
+            return Neighbour::Cell(x as usize, y as usize);
+        }
+        let (x, y) = match self {
+            BoarderPolicy::Ignored => return Neighbour::Dead,
+            BoarderPolicy::LiveBorder => return Neighbour::Alive,
+            BoarderPolicy::Looping => (x.rem_euclid(w), y.rem_euclid(h)),
+            BoarderPolicy::Reflecting => (mirror(x, w), mirror(y, h)),
+            BoarderPolicy::Klein => {
+                let x = if off_y { w - 1 - x } else { x };
+                (x.rem_euclid(w), y.rem_euclid(h))
+            }
+            BoarderPolicy::Projective => {
+                let (x, y) = if off_x {
+                    (x.rem_euclid(w), h - 1 - y)
+                } else {
+                    (x, y)
+                };
+                if y < 0 || y >= h {
+                    (w - 1 - x, y.rem_euclid(h))
+                } else {
+                    (x, y)
+                }
+            }
+        };
+        Neighbour::Cell(x as usize, y as usize)
+    }
+}
+/// Reflect a position less than `n` cells past either edge back inside.
+fn mirror(a: i32, n: i32) -> i32 {
+    if a < 0 {
+        -1 - a
+    } else if a >= n {
+        2 * n - 1 - a
+    } else {
+        a
+    }
 }
 pub struct LifeGame<const W: usize, const H: usize, R> {
-    state: [[CellState; H]; W],
-    state_next: [[CellState; H]; W],
+    state: Board<W, H>,
+    state_next: Board<W, H>,
+    /// Dying cells of the next generation, for Generations rules.
+    ages: Ages<W>,
+    /// Cells on dead or masked LEDs, one bit per row in each column.
+    unavailable: [u32; W],
     boarder_policy: BoarderPolicy,
+    rule: Rule,
+    cycles: CycleDetector,
+    /// Generations stepped since the board was last cleared.
+    generation: u32,
+    history: History,
+    /// What the soup on the board was made from, if it came from
+    /// `arrange_soup`.
+    soup_seed: Option<u32>,
+    /// Also count a board that comes back shifted on the torus as a cycle.
+    detect_translations: bool,
+    /// Generations since each cell of the next generation last changed
+    /// between dead and alive, only kept up while `heat_map` is on.
+    heat: Heat<W>,
+    /// Draw cells by `heat` instead of plainly alive or dead.
+    heat_map: bool,
+    /// Gray level each LED was last drawn at.
+    shown: [[u8; H]; W],
     sender: ICN2037Sender,
     fade_time_ms: u64,
     rng: R,
@@ -32,88 +161,90 @@ impl<const W: usize, const H: usize, R> LifeGame<W, H, R>
 where
     R: rand::RngCore,
 {
+    const H_FITS: () = assert!(H <= 32, "column bits are kept in a u32");
+
     pub fn new(sender: ICN2037Sender, fade_time: u64, rng: R) -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::H_FITS;
         Self {
-            state: [[Default::default(); H]; W],
-            state_next: [[Default::default(); H]; W],
+            state: Board::new(),
+            state_next: Board::new(),
+            ages: Ages::new(),
+            unavailable: [0; W],
             boarder_policy: Default::default(),
+            rule: Default::default(),
+            cycles: CycleDetector::new(64, 2),
+            generation: 0,
+            history: History::new(),
+            soup_seed: None,
+            detect_translations: true,
+            heat: Heat::new(),
+            heat_map: false,
+            shown: [[0; H]; W],
             sender,
             fade_time_ms: fade_time,
             rng,
         }
     }
+    pub fn set_boarder_policy(&mut self, policy: BoarderPolicy) {
+        self.boarder_policy = policy;
+        self.cycles.reset();
+    }
+    pub fn set_rule(&mut self, rule: Rule) {
+        self.rule = rule;
+        if rule.states <= 2 {
+            self.ages = Ages::new();
+        }
+    }
+    /// Report cycles of up to `max_period` generations once they have gone
+    /// round `max_repeats` times.
+    pub fn set_cycle_detection(&mut self, max_period: usize, max_repeats: u32, translations: bool) {
+        self.cycles = CycleDetector::new(max_period, max_repeats);
+        self.detect_translations = translations;
+    }
+    pub fn set_heat_map(&mut self, heat_map: bool) {
+        if heat_map && !self.heat_map {
+            // ages went stale while it was off
+            self.heat = Heat::new();
+        }
+        self.heat_map = heat_map;
+    }
     pub fn set_fade_time(&mut self, fade_time: u64) {
         self.fade_time_ms = fade_time;
     }
     pub async fn send_message(&mut self, msg: ICN2037Message) {
         self.sender.sender.send(msg).await;
     }
-    fn count_neighbors_alive(&self, x: usize, y: usize, map: &[[CellState; H]; W]) -> usize {
-        let mut r = 0;
-        match self.boarder_policy {
-            BoarderPolicy::Ignored => {
-                let sx = if x == 0 { 0 } else { x - 1 };
-                let ex = if x >= W - 1 { W - 1 } else { x + 1 };
-                let sy = if y == 0 { 0 } else { y - 1 };
-                let ey = if y >= H - 1 { H - 1 } else { y + 1 };
-                for xx in sx..=ex {
-                    for yy in sy..=ey {
-                        if !(xx == x && yy == y) {
-                            r += map[xx][yy] as usize;
-                        }
-                    }
-                }
-            }
-            BoarderPolicy::Looping => {
-                let sx = x as i32 - 1;
-                let ex = x as i32 + 1;
-                let sy = y as i32 - 1;
-                let ey = y as i32 + 1;
-                let mapping = |a, b| {
-                    (
-                        if a < 0 {
-                            a as usize + W
-                        } else {
-                            if a as usize >= W {
-                                a as usize - W
-                            } else {
-                                a as usize
-                            }
-                        },
-                        if b < 0 {
-                            b as usize + H
-                        } else {
-                            if b as usize >= H {
-                                b as usize - H
-                            } else {
-                                b as usize
-                            }
-                        },
-                    )
-                };
-                for xx in sx..=ex {
-                    for yy in sy..=ey {
-                        let (xx, yy) = mapping(xx, yy);
-                        if !(xx == x && yy == y) {
-                            r += map[xx][yy] as usize;
-                        }
-                    }
-                }
-            }
+    pub async fn send_control(&mut self, msg: ICN2037Control) {
+        self.sender.control.send(msg).await;
+    }
+    /// Treat the cell as unavailable, it is kept dead from now on.
+    pub fn set_unavailable(&mut self, x: usize, y: usize, unavailable: bool) {
+        if x >= W || y >= H {
+            return;
+        }
+        if unavailable {
+            self.unavailable[x] |= 1 << y;
+            self.state_next.set(x, y, false);
+            self.ages.set(x, y, 0);
+        } else {
+            self.unavailable[x] &= !(1 << y);
         }
-        r
+    }
+    pub fn is_unavailable(&self, x: usize, y: usize) -> bool {
+        self.unavailable[x] & (1 << y) != 0
     }
     pub fn all_dead(&self) -> bool {
-        self.state
-            .iter()
-            .all(|m| m.iter().all(|x| *x == CellState::Dead))
+        self.state.is_empty()
     }
     pub fn all_dead_next(&self) -> bool {
-        self.state_next
-            .iter()
-            .all(|m| m.iter().all(|x| *x == CellState::Dead))
+        self.state_next.is_empty() && self.ages.is_empty()
     }
     pub fn is_still(&self) -> bool {
+        // dying cells still fade out
+        if !self.ages.is_empty() {
+            return false;
+        }
         if self.state == self.state_next {
             return true;
         }
@@ -121,46 +252,93 @@ where
             return true;
         }
         // detect 2 cycle
-        let mut next = [[CellState::Dead; H]; W];
-        self.step_calc(&self.state_next, &mut next);
+        let mut next = Board::new();
+        self.step_calc(&self.state_next, &mut next, &mut Ages::new());
         if self.state == next {
             return true;
         }
         false
     }
-    pub fn step_calc(&self, last: &[[CellState; H]; W], next: &mut [[CellState; H]; W]) {
-        for x in 0..W {
-            for y in 0..H {
-                let count = self.count_neighbors_alive(x, y, last);
-                use CellState::*;
-                let next_state = match (last[x][y], count) {
-                    (Alive, v) if v < 2 => Dead,
-                    (Alive, 2) | (Alive, 3) => Alive,
-                    (Alive, v) if v > 3 => Dead,
-                    (Dead, 3) => Alive,
-                    (otherwise, _) => otherwise,
-                };
-                next[x][y] = next_state;
-            }
-        }
+    /// Feed the next generation to the cycle detector, returning the period
+    /// once the board is found repeating itself.
+    pub fn is_cycling(&mut self) -> Option<usize> {
+        let translate =
+            self.detect_translations && matches!(self.boarder_policy, BoarderPolicy::Looping);
+        self.cycles
+            .push(self.state_next.hash(&self.ages, translate))
+    }
+    pub fn step_calc(&self, last: &Board<W, H>, next: &mut Board<W, H>, ages: &mut Ages<W>) {
+        last.step(
+            next,
+            ages,
+            &self.rule,
+            &self.boarder_policy,
+            &self.unavailable,
+        );
     }
 
     pub fn step(&mut self) {
-        let last = &self.state;
-        for x in 0..W {
-            for y in 0..H {
-                let count = self.count_neighbors_alive(x, y, last);
-                use CellState::*;
-                let next_state = match (last[x][y], count) {
-                    (Alive, v) if v < 2 => Dead,
-                    (Alive, 2) | (Alive, 3) => Alive,
-                    (Alive, v) if v > 3 => Dead,
-                    (Dead, 3) => Alive,
-                    (otherwise, _) => otherwise,
-                };
-                self.state_next[x][y] = next_state;
-            }
+        self.state.step(
+            &mut self.state_next,
+            &mut self.ages,
+            &self.rule,
+            &self.boarder_policy,
+            &self.unavailable,
+        );
+        self.generation += 1;
+        if self.heat_map {
+            self.heat.step(&self.state, &self.state_next);
         }
+        let (births, deaths) = self.state.changes(&self.state_next);
+        self.history.push(GenStats {
+            generation: self.generation,
+            population: self.state_next.population() as u16,
+            births: births as u16,
+            deaths: deaths as u16,
+        });
+    }
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+    pub fn soup_seed(&self) -> Option<u32> {
+        self.soup_seed
+    }
+    /// The next generation, as drawn, with its dying cells under a
+    /// Generations rule, and its generation count.
+    pub fn snapshot(&self) -> (Board<W, H>, Option<Ages<W>>, u32) {
+        let ages = (self.rule.states > 2).then_some(self.ages);
+        (self.state_next, ages, self.generation)
+    }
+    /// Carry on from a board taken by `snapshot`, which came from the soup
+    /// `soup_seed`. Cell ages for the heat map start over.
+    pub fn resume(
+        &mut self,
+        board: Board<W, H>,
+        ages: Option<Ages<W>>,
+        generation: u32,
+        soup_seed: Option<u32>,
+    ) {
+        self.clear();
+        self.state = board;
+        self.state_next = board;
+        self.ages = ages.unwrap_or_default();
+        self.generation = generation;
+        self.soup_seed = soup_seed;
+    }
+    /// Re-seed the RNG from its own output and return the seed, so an RNG
+    /// made with `R::from_seed` goes on with the same numbers.
+    pub fn checkpoint_rng(&mut self) -> R::Seed
+    where
+        R: rand::SeedableRng,
+        R::Seed: Clone,
+    {
+        let mut seed = R::Seed::default();
+        self.rng.fill_bytes(seed.as_mut());
+        self.rng = R::from_seed(seed.clone());
+        seed
     }
     pub fn step_apply(&mut self) {
         self.state = self.state_next;
@@ -168,86 +346,206 @@ where
     pub fn make_alive(&mut self, x: usize, y: usize, alive: bool) {
         let x = x % W;
         let y = y % H;
-        self.state_next[x][y] = if alive {
-            CellState::Alive
-        } else {
-            CellState::Dead
-        };
+        let alive = alive && !self.is_unavailable(x, y);
+        if self.state_next.is_alive(x, y) != alive {
+            self.heat.reset(x, y);
+        }
+        self.state_next.set(x, y, alive);
+        self.ages.set(x, y, 0);
     }
-    pub fn apply_pattern(&mut self, x: usize, y: usize, pattern: &[&str]) {
-        for (dy, line) in pattern.iter().enumerate() {
-            for (dx, c) in line.chars().enumerate() {
-                let x = x + dx;
-                let y = y + dy;
-                if x < W && y < H {
-                    self.make_alive(x, y, c != ' ');
+    /// Put `pattern` on the next generation with its top left corner at
+    /// (`x`, `y`). Nothing is written unless the whole pattern fits.
+    pub fn stamp(
+        &mut self,
+        x: usize,
+        y: usize,
+        pattern: &PatternBuf,
+        mode: StampMode,
+    ) -> Result<(), StampError> {
+        let (w, h) = (pattern.width as usize, pattern.height as usize);
+        if w > W || h > H {
+            return Err(StampError::TooLarge);
+        }
+        if !mode.wrap && (x + w > W || y + h > H) {
+            return Err(StampError::OutOfBounds);
+        }
+        if let Some(margin) = mode.margin {
+            if self.collides(x, y, pattern, margin as i32, mode.wrap) {
+                return Err(StampError::Collision);
+            }
+        }
+        for dy in 0..h {
+            for dx in 0..w {
+                let alive = pattern.get(dx, dy);
+                if alive || !mode.or {
+                    self.make_alive(x + dx, y + dy, alive);
                 }
             }
         }
+        Ok(())
     }
-    pub fn apply_pattern_transpose(&mut self, x: usize, y: usize, pattern: &[&str]) {
-        for (dy, line) in pattern.iter().enumerate() {
-            for (dx, c) in line.chars().enumerate() {
-                let x = x + dx;
-                let y = y + dy;
-                if x < W && y < H {
-                    self.make_alive(y, x, c != ' ');
+    /// `stamp` with the pattern centred on the board.
+    pub fn stamp_center(
+        &mut self,
+        pattern: &PatternBuf,
+        mode: StampMode,
+    ) -> Result<(), StampError> {
+        let (w, h) = (pattern.width as usize, pattern.height as usize);
+        if w > W || h > H {
+            return Err(StampError::TooLarge);
+        }
+        self.stamp((W - w) / 2, (H - h) / 2, pattern, mode)
+    }
+    /// Whether a live cell of the next generation is within `margin` cells
+    /// of a live cell of `pattern` placed at (`x`, `y`).
+    fn collides(&self, x: usize, y: usize, pattern: &PatternBuf, margin: i32, wrap: bool) -> bool {
+        for py in 0..pattern.height as i32 {
+            for px in 0..pattern.width as i32 {
+                if !pattern.get(px as usize, py as usize) {
+                    continue;
+                }
+                for dy in -margin..=margin {
+                    for dx in -margin..=margin {
+                        let (cx, cy) = (x as i32 + px + dx, y as i32 + py + dy);
+                        let (cx, cy) = if wrap {
+                            (cx.rem_euclid(W as i32), cy.rem_euclid(H as i32))
+                        } else if cx < 0 || cy < 0 || cx >= W as i32 || cy >= H as i32 {
+                            continue;
+                        } else {
+                            (cx, cy)
+                        };
+                        if self.state_next.is_alive(cx as usize, cy as usize) {
+                            return true;
+                        }
+                    }
                 }
             }
         }
+        false
     }
-    pub fn apply_pattern_center(&mut self, pattern: &[&str]) {
-        let (w, h) = (pattern[0].len(), pattern.len());
-        let (x, y) = (W / 2 - w / 2, H / 2 - h / 2);
-        self.apply_pattern(x, y, pattern);
+    pub fn clear(&mut self) {
+        self.state = Board::new();
+        self.state_next = Board::new();
+        self.ages = Ages::new();
+        self.cycles.reset();
+        self.generation = 0;
+        self.history.reset();
+        self.soup_seed = None;
+        self.heat = Heat::new();
+        // what is shown stays as it was unless the clear gets through, so
+        // the next draw still overwrites it
+        match self.sender.try_clear() {
+            Ok(()) => self.shown = [[0; H]; W],
+            Err(_) => defmt::warn!("display queues full, clear dropped"),
+        }
     }
-    pub fn apply_pattern_center_transpose(&mut self, pattern: &[&str]) {
-        let (w, h) = (pattern[0].len(), pattern.len());
-        let (x, y) = (W / 2 - w / 2, H / 2 - h / 2);
-        self.apply_pattern_transpose(x, y, pattern);
+    /// Clear the board and spawn a soup from a new random seed.
+    pub fn randomly_arrange_patterns(&mut self)
+    where
+        R: rand::SeedableRng,
+    {
+        let seed = self.rng.next_u32();
+        self.arrange_soup(seed);
     }
-    pub fn clear(&mut self) {
-        self.state
-            .iter_mut()
-            .for_each(|m| m.iter_mut().for_each(|x| *x = CellState::Dead));
-        self.state_next
-            .iter_mut()
-            .for_each(|m| m.iter_mut().for_each(|x| *x = CellState::Dead));
-        self.sender.sender.try_send(ICN2037Message::Clear).unwrap();
-    }
-    pub fn randomly_arrange_patterns(&mut self) {
+    /// Clear the board and spawn the soup made from `seed`, the same one
+    /// every time and whatever the edge. Patterns are never stamped across
+    /// an edge, so the placement does not depend on `boarder_policy`.
+    pub fn arrange_soup(&mut self, seed: u32)
+    where
+        R: rand::SeedableRng,
+    {
         self.clear();
-        let mut picks = [0; 3];
-        self.rng.fill_bytes(&mut picks);
-        picks.iter_mut().for_each(|x| *x = *x % 3);
-        let patterns = [PATTERN_CLOCK_LIST, PATTERN_FLY_LIST, PATTERN_STABLE_LIST];
-        for k in 0..3 {
-            for i in 0..picks[k] as usize {
-                let pattern = patterns[i];
-                let x = self.rng.next_u32() as usize % W;
-                let y = self.rng.next_u32() as usize % H;
-                let idx = self.rng.next_u32() as usize % pattern.len();
-                self.apply_pattern(x, y, pattern[idx]);
+        self.soup_seed = Some(seed);
+        defmt::info!("soup {=u32:08x}", seed);
+        let mut rng = R::seed_from_u64(seed as u64);
+        let mode = StampMode {
+            wrap: false,
+            or: true,
+            margin: Some(1),
+        };
+        let mut picks = [0; Category::ALL.len()];
+        rng.fill_bytes(&mut picks);
+        for (category, pick) in Category::ALL.into_iter().zip(picks) {
+            let fits = |p: &&Pattern| p.width.max(p.height) as usize <= W.min(H);
+            let count = patterns_in(category).filter(fits).count();
+            if count == 0 {
+                continue;
+            }
+            for _ in 0..pick % 3 {
+                let idx = rng.next_u32() as usize % count;
+                let orientation = Orientation::ALL[rng.next_u32() as usize % 8];
+                let Some(pattern) = patterns_in(category).filter(fits).nth(idx) else {
+                    continue;
+                };
+                let buf = pattern.unpack().oriented(orientation);
+                // only pick corners that keep it on the board
+                let (w, h) = (W - buf.width as usize + 1, H - buf.height as usize + 1);
+                for _ in 0..4 {
+                    let x = rng.next_u32() as usize % w;
+                    let y = rng.next_u32() as usize % h;
+                    match self.stamp(x, y, &buf, mode) {
+                        Ok(()) => {
+                            let (dx, dy) = orientation
+                                .apply(pattern.velocity.0 as i32, pattern.velocity.1 as i32);
+                            defmt::info!(
+                                "spawn {} ({}) at {}, {} heading {}, {}",
+                                pattern.name,
+                                category,
+                                x,
+                                y,
+                                dx,
+                                dy
+                            );
+                            break;
+                        }
+                        Err(e) => {
+                            defmt::debug!("no room for {} at {}, {}: {}", pattern.name, x, y, e)
+                        }
+                    }
+                }
             }
         }
     }
+    /// State of a cell of the next generation.
+    fn cell(&self, x: usize, y: usize) -> CellState {
+        if self.state_next.is_alive(x, y) {
+            return CellState::Alive;
+        }
+        match self.ages.get(x, y) {
+            0 => CellState::Dead,
+            age => CellState::Dying(age),
+        }
+    }
+    /// Gray level the cell of the next generation is drawn at.
+    fn level(&self, x: usize, y: usize) -> u8 {
+        let cell = self.cell(x, y);
+        if !self.heat_map {
+            return self.rule.gray_level(cell);
+        }
+        let age = self.heat.get(x, y);
+        match cell {
+            // newborn cells are brightest, long lived ones dim down
+            CellState::Alive => 15 - age.min(11),
+            // a short trail where cells have just died
+            CellState::Dead => [6, 3, 1].get(age as usize).copied().unwrap_or(0),
+            CellState::Dying(_) => self.rule.gray_level(cell),
+        }
+    }
     pub async fn draw(&mut self, quick: bool) {
         let k_max = 15;
-        let send = |k, x, y, from, to| {
-            let v = match (from, to) {
-                (CellState::Dead, CellState::Alive) => Some(k),
-                (CellState::Alive, CellState::Dead) => Some(k_max - k),
-                (CellState::Alive, CellState::Alive) => None,
-                (CellState::Dead, CellState::Dead) => None,
-            };
-            v.map(|v| ICN2037Message::SetPixel((x, y, v)))
+        let send = |k: i16, x, y, from: u8, to: u8| {
+            if from == to {
+                return None;
+            }
+            let (from, to) = (from as i16, to as i16);
+            let v = from + (to - from) * k / k_max;
+            Some(ICN2037Message::SetPixel((x, y, v as u8)))
         };
         if self.fade_time_ms >= 16 && !quick {
             for k in 1..=k_max {
-                for x in 0..25 {
-                    for y in 0..16 {
-                        let (from, to) = (self.state[x][y], self.state_next[x][y]);
-                        if let Some(msg) = send(k, x, y, from, to) {
+                for x in 0..W {
+                    for y in 0..H {
+                        if let Some(msg) = send(k, x, y, self.shown[x][y], self.level(x, y)) {
                             self.sender.sender.send(msg).await;
                         }
                     }
@@ -255,10 +553,9 @@ where
                 Timer::after_millis(self.fade_time_ms / (k_max as u64 + 1)).await;
             }
         } else {
-            for x in 0..25 {
-                for y in 0..16 {
-                    let (from, to) = (self.state[x][y], self.state_next[x][y]);
-                    if let Some(msg) = send(15, x, y, from, to) {
+            for x in 0..W {
+                for y in 0..H {
+                    if let Some(msg) = send(k_max, x, y, self.shown[x][y], self.level(x, y)) {
                         self.sender.sender.send(msg).await;
                     }
                 }
@@ -269,5 +566,10 @@ where
                 Timer::after_millis(self.fade_time_ms).await;
             }
         }
+        for x in 0..W {
+            for y in 0..H {
+                self.shown[x][y] = self.level(x, y);
+            }
+        }
     }
 }
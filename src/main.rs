@@ -2,6 +2,9 @@
 #![no_main]
 #![feature(type_alias_impl_trait)]
 
+use core::fmt::Write as _;
+
+use board::{Ages, Board};
 use defmt::*;
 use embassy_executor::Spawner;
 use embassy_stm32::{
@@ -13,29 +16,40 @@ use embassy_stm32::{
 };
 use embassy_sync::{
     blocking_mutex::raw::NoopRawMutex,
-    channel::{Channel, Receiver, Sender},
+    channel::{Channel, Receiver, Sender, TryReceiveError},
 };
 use embassy_time::{Delay, Duration, Instant, Timer};
 use embedded_graphics::{
     draw_target::DrawTarget,
     geometry::Point,
     mono_font::{ascii, MonoTextStyleBuilder},
-    pixelcolor::Gray4,
+    pixelcolor::{Gray4, GrayColor},
     text::Text,
     Drawable,
 };
 use embedded_hal::digital::{InputPin, OutputPin};
 use embedded_storage::nor_flash::{NorFlash, ReadNorFlash};
 use futures::Future;
-use icn2037::{ICN2037Device, ICN2037Receiver, ICN2037Sender};
-use lifegame::LifeGame;
+use hall::{HallOfFame, Record, HALL_LEN};
+use icn2037::{
+    ICN2037Control, ICN2037ControlReceiver, ICN2037Device, ICN2037Receiver, ICN2037Sender,
+};
+use lifegame::{BoarderPolicy, LifeGame};
 use rand::SeedableRng;
 use rand_xorshift::XorShiftRng;
+use rule::{Rule, RULES};
 use static_cell::make_static;
+use stats::LIFETIME_LEN;
 use {defmt_rtt as _, panic_probe as _};
 
+mod board;
+mod cycle;
+mod hall;
 mod lifegame;
+mod pattern_format;
 mod patterns;
+mod rule;
+mod stats;
 
 // DIN = PB5
 // CLK = PB3
@@ -57,6 +71,7 @@ async fn main(spawner: Spawner) {
     let spi = Spi::new_txonly(p.SPI1, p.PB3, p.PB5, NoDma, NoDma, spi_config);
 
     let buffer = make_static!([0u16; 25 * 16]);
+    let lut = make_static!([icn2037::PixelAddr::default(); 25 * 16]);
     let (width, height) = (25, 16);
     let mut icn = icn2037::ICN2037::new(
         spi,
@@ -76,20 +91,37 @@ async fn main(spawner: Spawner) {
             (idx, offset)
         }),
         buffer.as_mut(),
+        lut.as_mut(),
     );
 
     icn.start().unwrap();
 
+    let mut flash = Flash::new_blocking(p.FLASH)
+        .into_blocking_regions()
+        .bank1_region;
+    let calibration = make_static!([0u8; 25 * 16]);
+    let calibrated = load_calibration(&mut flash, calibration);
+    let calibration: &'static [u8] = calibration;
+    if calibrated {
+        icn.set_calibration(calibration);
+    }
+    // before anything is drawn, so shorted LEDs are never driven
+    let pixel_mask = PixelMask::load(&mut flash);
+    icn.set_pixel_mask(make_static!(pixel_mask.0));
+
     icn.set_pixel_gray(0, 0, 1);
     let icn_channel = &*make_static!(Channel::new());
     let (tx, rx) = (icn_channel.sender(), icn_channel.receiver());
+    let control_channel = &*make_static!(Channel::new());
+    let (control_tx, control_rx) = (control_channel.sender(), control_channel.receiver());
 
     let sender = ICN2037Sender {
-        config: icn.config.clone(),
+        config: icn.config().clone(),
         sender: tx,
+        control: control_tx,
     };
 
-    spawner.spawn(daemon_task(icn, rx)).unwrap();
+    spawner.spawn(daemon_task(icn, rx, control_rx)).unwrap();
 
     let mut icn = sender;
     icn.clear(Default::default()).unwrap();
@@ -109,14 +141,13 @@ async fn main(spawner: Spawner) {
         Input::new(p.PA10, embassy_stm32::gpio::Pull::Up),
         Output::new(p.PA9, Level::Low, Speed::Low),
     ));
+    // holding A at power on enters the diagnostics page
+    let (diagnostics, _) = (&mut *keys).read_keys().await;
     let keys_channel = &*make_static!(Channel::new());
     let (tx, rx) = (keys_channel.sender(), keys_channel.receiver());
     spawner.spawn(keys_task(keys, tx)).unwrap();
 
     let addr: u32 = STATE_ADDR;
-    let mut flash = Flash::new_blocking(p.FLASH)
-        .into_blocking_regions()
-        .bank1_region;
     let mut magic_buf = [0u8; 8];
     flash.blocking_read(addr, &mut magic_buf).unwrap();
     let magic = u64::from_le_bytes(magic_buf);
@@ -140,12 +171,15 @@ async fn main(spawner: Spawner) {
         defmt::warn!("state version mismatch, reset state");
         state = State::default_with_flash(state.flash.take().unwrap());
     }
+    if let Page::Diagnostics | Page::Settings | Page::Seed | Page::Hall = state.page {
+        state.page = Page::Game;
+    }
 
     let title_brightness = state.game_brightness.max(state.light_brightness);
     defmt::info!("title brightness: {}", title_brightness);
 
-    icn.sender
-        .send(icn2037::ICN2037Message::SetBrightness(title_brightness))
+    icn.control
+        .send(ICN2037Control::SetBrightness(title_brightness))
         .await;
 
     // TEXT:
@@ -159,7 +193,9 @@ async fn main(spawner: Spawner) {
     for texts in texts_list {
         Timer::after_millis(80 * 3).await;
         for i in 0..((texts[0].len().max(texts[1].len()) - 5) * 5) as i32 {
-            icn.clear(Default::default()).unwrap();
+            if icn.clear(Default::default()).is_err() {
+                warn!("display queues full, clear dropped");
+            }
             Text::with_alignment(
                 texts[0],
                 Point::new(0 - i, 5),
@@ -200,21 +236,147 @@ async fn main(spawner: Spawner) {
         Timer::after_millis(80 * 5).await;
     }
 
-    icn.sender
-        .send(icn2037::ICN2037Message::SetBrightness(15))
-        .await;
+    icn.control.send(ICN2037Control::SetBrightness(15)).await;
 
     state.save().await;
 
+    if diagnostics {
+        info!("enter diagnostics");
+        state.page = Page::Diagnostics;
+    }
+
     let rng = XorShiftRng::from_seed(adc_results);
-    let mut game = Game::new(icn.clone(), rx, rng, state);
+    let mut game = Game::new(icn.clone(), rx, rng, state, pixel_mask);
     game.run().await;
     info!("Fin.");
 }
 
+const CALIBRATION_ADDR: u32 = 1024 * 112;
+const CALIBRATION_MAGIC: u64 = 0xca11b4a7e;
+
+/// Per LED correction table, written separately at `CALIBRATION_ADDR` as
+/// `CALIBRATION_MAGIC` (little endian u64) followed by one byte per LED in
+/// `x * 16 + y` order, 255 meaning full brightness.
+fn load_calibration<F: ReadNorFlash>(flash: &mut F, table: &mut [u8]) -> bool {
+    let mut magic_buf = [0u8; 8];
+    flash.read(CALIBRATION_ADDR, &mut magic_buf).unwrap();
+    if u64::from_le_bytes(magic_buf) != CALIBRATION_MAGIC {
+        defmt::info!("no calibration table");
+        return false;
+    }
+    flash.read(CALIBRATION_ADDR + 8, table).unwrap();
+    defmt::info!("calibration table loaded");
+    true
+}
+
+const MASK_ADDR: u32 = 1024 * 116;
+const MASK_MAGIC: u64 = 0xdead_1ed5;
+
+/// Dead or stuck LEDs, one bit per pixel in `x * 16 + y` order.
+///
+/// Written at `MASK_ADDR` as `MASK_MAGIC` (little endian u64) followed by
+/// the words, apart from `State` so a firmware update does not forget it.
+#[derive(Clone, Copy)]
+pub struct PixelMask([u32; MASK_WORDS]);
+
+impl PixelMask {
+    const BYTES: usize = (8 + MASK_WORDS * 4).next_multiple_of(8);
+
+    fn load<F: ReadNorFlash>(flash: &mut F) -> Self {
+        let mut buf = [0u8; Self::BYTES];
+        flash.read(MASK_ADDR, &mut buf).unwrap();
+        let mut mask = Self([0; MASK_WORDS]);
+        if u64::from_le_bytes(buf[..8].try_into().unwrap()) != MASK_MAGIC {
+            defmt::info!("no pixel mask");
+            return mask;
+        }
+        for (word, bytes) in mask.0.iter_mut().zip(buf[8..].chunks_exact(4)) {
+            *word = u32::from_le_bytes(bytes.try_into().unwrap());
+        }
+        mask
+    }
+
+    fn save<F: NorFlash>(&self, flash: &mut F) {
+        let mut buf = [0xffu8; Self::BYTES];
+        buf[..8].copy_from_slice(&MASK_MAGIC.to_le_bytes());
+        for (word, bytes) in self.0.iter().zip(buf[8..].chunks_exact_mut(4)) {
+            bytes.copy_from_slice(&word.to_le_bytes());
+        }
+        flash.erase(MASK_ADDR, MASK_ADDR + 2048).unwrap();
+        flash.write(MASK_ADDR, &buf).unwrap();
+    }
+
+    pub fn is_masked(&self, x: usize, y: usize) -> bool {
+        let i = x * 16 + y;
+        self.0[i / 32] & (1 << (i % 32)) != 0
+    }
+
+    pub fn toggle(&mut self, x: usize, y: usize) -> bool {
+        let i = x * 16 + y;
+        self.0[i / 32] ^= 1 << (i % 32);
+        self.is_masked(x, y)
+    }
+}
+
+const HALL_ADDR: u32 = 1024 * 118;
+/// Changes whenever the layout of the hall of fame page does, so an old one
+/// reads as empty.
+const HALL_MAGIC: u64 = 0x4a11_0ffa_3e01;
+const HALL_RECORD_BYTES: usize = 12;
+const HALL_BYTES: usize = (8 + 4 + HALL_LEN * HALL_RECORD_BYTES).next_multiple_of(8);
+
+/// The hall of fame, written at `HALL_ADDR` as `HALL_MAGIC` (little endian
+/// u64), the number of records (u32) and the records, each seed,
+/// generations (both u32), rule and edge index and two bytes of padding.
+/// Kept apart from `State` so a firmware update does not forget it.
+fn load_hall_of_fame<F: ReadNorFlash>(flash: &mut F) -> HallOfFame {
+    let mut buf = [0u8; HALL_BYTES];
+    flash.read(HALL_ADDR, &mut buf).unwrap();
+    let mut hall = HallOfFame::new();
+    if u64::from_le_bytes(buf[..8].try_into().unwrap()) != HALL_MAGIC {
+        defmt::info!("no hall of fame");
+        return hall;
+    }
+    let len = u32::from_le_bytes(buf[8..12].try_into().unwrap()) as usize;
+    for bytes in buf[12..]
+        .chunks_exact(HALL_RECORD_BYTES)
+        .take(len.min(HALL_LEN))
+    {
+        hall.insert(Record {
+            seed: u32::from_le_bytes(bytes[..4].try_into().unwrap()),
+            generations: u32::from_le_bytes(bytes[4..8].try_into().unwrap()),
+            rule_idx: bytes[8],
+            border_idx: bytes[9],
+        });
+    }
+    hall
+}
+
+fn save_hall_of_fame<F: NorFlash>(flash: &mut F, hall: &HallOfFame) {
+    let mut buf = [0xffu8; HALL_BYTES];
+    buf[..8].copy_from_slice(&HALL_MAGIC.to_le_bytes());
+    buf[8..12].copy_from_slice(&(hall.entries().len() as u32).to_le_bytes());
+    for (record, bytes) in hall
+        .entries()
+        .iter()
+        .zip(buf[12..].chunks_exact_mut(HALL_RECORD_BYTES))
+    {
+        bytes[..4].copy_from_slice(&record.seed.to_le_bytes());
+        bytes[4..8].copy_from_slice(&record.generations.to_le_bytes());
+        bytes[8] = record.rule_idx;
+        bytes[9] = record.border_idx;
+    }
+    flash.erase(HALL_ADDR, HALL_ADDR + 2048).unwrap();
+    flash.write(HALL_ADDR, &buf).unwrap();
+}
+
 #[embassy_executor::task]
-async fn daemon_task(dev: impl ICN2037Device + 'static, receiver: ICN2037Receiver) {
-    dev.task(receiver).await.unwrap();
+async fn daemon_task(
+    dev: impl ICN2037Device + 'static,
+    receiver: ICN2037Receiver,
+    control: ICN2037ControlReceiver,
+) {
+    dev.task(receiver, control).await.unwrap();
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -317,12 +479,114 @@ pub enum Page {
     #[default]
     Game,
     Light,
+    Diagnostics,
+    Settings,
+    /// Type in a soup seed to replay it.
+    Seed,
+    /// Look through the hall of fame and replay its soups.
+    Hall,
+}
+
+/// Items of the settings page, A steps through them and B changes the value.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Setting {
+    Rule,
+    Border,
+    Stats,
+    /// Draw cells by how long they have lived.
+    Heat,
+    /// Opens the seed editor.
+    Seed,
+    /// Opens the hall of fame.
+    Best,
 }
+const SETTINGS: &[Setting] = &[
+    Setting::Rule,
+    Setting::Border,
+    Setting::Stats,
+    Setting::Heat,
+    Setting::Seed,
+    Setting::Best,
+];
+
+/// What the game page shows of the population history.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum StatsMode {
+    Off,
+    /// The generation count and a population graph before every re-seed.
+    Reseed,
+    /// A population graph of the last 25 generations instead of the board.
+    Graph,
+}
+impl StatsMode {
+    const ALL: [StatsMode; 3] = [StatsMode::Off, StatsMode::Reseed, StatsMode::Graph];
+    fn name(&self) -> &'static str {
+        match self {
+            StatsMode::Off => "OFF",
+            StatsMode::Reseed => "RESEED",
+            StatsMode::Graph => "GRAPH",
+        }
+    }
+}
+/// Longest cycle looked for before re-generating, and how many times
+/// the board may go round it first.
+const CYCLE_MAX_PERIOD: usize = 64;
+const CYCLE_REPEATS: u32 = 2;
+/// How often where the soup got to is logged, on top of leaving the game
+/// page. The log page is only erased once every `SOUP_LOG_ENTRIES` entries.
+const SOUP_LOG_INTERVAL: Duration = Duration::from_secs(10 * 60);
 
 const STATE_ADDR: u32 = 1024 * 110;
 const STATE_MAGIC: u64 = 0x1145141919810;
-const STATE_SIZE: usize = 512;
+const STATE_SIZE: usize = 1024;
+const MASK_WORDS: usize = (25 * 16usize).div_ceil(32);
 const STATE_VERSION: &str = build_info::format!("v{}-{}", $.crate_info.version, $.version_control.unwrap().git().unwrap().commit_short_id);
+/// A game left on the game page, picked up again on the next visit.
+#[derive(Clone, Copy)]
+pub struct SavedGame {
+    board: Board<25, 16>,
+    /// Dying cells, only kept under a Generations rule.
+    ages: Option<Ages<25>>,
+    generation: u32,
+    soup_seed: Option<u32>,
+}
+
+const SOUP_LOG_ADDR: u32 = 1024 * 114;
+const SOUP_LOG_SIZE: usize = 2048;
+const SOUP_LOG_MAGIC: u32 = 0x5009_1066;
+const SOUP_ENTRY_SIZE: usize = core::mem::size_of::<SoupEntry>();
+const SOUP_AGES_SIZE: usize = core::mem::size_of::<Ages<25>>();
+const _: () = core::assert!(
+    SOUP_ENTRY_SIZE.is_multiple_of(8) && SOUP_AGES_SIZE.is_multiple_of(8),
+    "soup log entries are written in double words"
+);
+/// The board on the game page and where its soup had got to, to carry on
+/// from after a reboot. Entries are appended to the page at
+/// `SOUP_LOG_ADDR`, which is erased only when it is full, instead of
+/// rewriting `State`.
+///
+/// Under a Generations rule the entry is followed by the board's `Ages`,
+/// then comes a double word starting with `SOUP_LOG_MAGIC`, written last
+/// so a torn entry is skipped.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SoupEntry {
+    /// 1 when `Ages` follow, first so the next entry can be found even
+    /// when this one is torn.
+    dying: u8,
+    /// Index into `RULES`.
+    rule_idx: u8,
+    /// Index into `BoarderPolicy::ALL`.
+    border_idx: u8,
+    _reserved: u8,
+    soup_seed: u32,
+    generation: u32,
+    /// Seed the RNG was restarted from when the entry was logged.
+    rng_seed: [u8; 16],
+    board: Board<25, 16>,
+}
 #[repr(C)]
 #[repr(align(1))]
 pub struct State<F> {
@@ -333,6 +597,14 @@ pub struct State<F> {
     light_brightness: u8,
     serial_mode: bool,
     fade_time_ms: u64,
+    /// Index into `RULES`.
+    rule_idx: u8,
+    /// Index into `BoarderPolicy::ALL`.
+    border_idx: u8,
+    /// Index into `StatsMode::ALL`.
+    stats_idx: u8,
+    /// Draw the game as a heat map of cell ages.
+    heat_map: bool,
     pub flash: Option<F>,
 }
 impl<F> Default for State<F> {
@@ -350,6 +622,10 @@ impl<F> Default for State<F> {
             light_brightness: 15,
             serial_mode: false,
             fade_time_ms: 16 * 20,
+            rule_idx: 0,
+            border_idx: 0,
+            stats_idx: 0,
+            heat_map: false,
             flash: None,
         }
     }
@@ -370,6 +646,11 @@ impl<F> State<F>
 where
     F: NorFlash + ReadNorFlash,
 {
+    const FITS: () = core::assert!(
+        core::mem::size_of::<Self>() <= STATE_SIZE,
+        "state does not fit in STATE_SIZE"
+    );
+
     pub fn default_with_flash(flash: F) -> Self {
         Self {
             flash: Some(flash),
@@ -382,6 +663,8 @@ where
         }
     }
     pub async fn save(&mut self) {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::FITS;
         let mut flash = self.flash.take().unwrap();
         let mut buf = [0u8; STATE_SIZE];
         unsafe {
@@ -392,31 +675,406 @@ where
         Timer::after_millis(100).await;
         self.flash.replace(flash);
     }
+    /// The last complete entry of the soup log with the `Ages` logged
+    /// along, and the offset after the last one written, `SOUP_LOG_SIZE`
+    /// when there is no room left or the rest cannot be read.
+    fn scan_soup_log(flash: &mut F) -> (Option<(SoupEntry, Option<Ages<25>>)>, usize) {
+        let mut last = None;
+        let mut offset = 0;
+        while offset + SOUP_ENTRY_SIZE + 8 <= SOUP_LOG_SIZE {
+            let addr = SOUP_LOG_ADDR + offset as u32;
+            let mut buf = [0u8; SOUP_ENTRY_SIZE];
+            flash.read(addr, &mut buf).unwrap();
+            if buf.iter().all(|b| *b == 0xff) {
+                return (last, offset);
+            }
+            let entry: SoupEntry = unsafe { core::mem::transmute_copy(&buf) };
+            let ages_size = match entry.dying {
+                0 => 0,
+                1 => SOUP_AGES_SIZE,
+                // torn before its length went in, nothing after it is found
+                _ => break,
+            };
+            let len = SOUP_ENTRY_SIZE + ages_size + 8;
+            if offset + len > SOUP_LOG_SIZE {
+                break;
+            }
+            let mut ages = None;
+            if ages_size > 0 {
+                let mut buf = [0u8; SOUP_AGES_SIZE];
+                flash.read(addr + SOUP_ENTRY_SIZE as u32, &mut buf).unwrap();
+                ages = Some(unsafe { core::mem::transmute_copy(&buf) });
+            }
+            let mut magic = [0u8; 4];
+            flash.read(addr + (len - 8) as u32, &mut magic).unwrap();
+            if u32::from_le_bytes(magic) == SOUP_LOG_MAGIC {
+                last = Some((entry, ages));
+            }
+            offset += len;
+        }
+        (last, SOUP_LOG_SIZE)
+    }
+    pub fn last_soup(&mut self) -> Option<(SoupEntry, Option<Ages<25>>)> {
+        let mut flash = self.flash.take().unwrap();
+        let (last, _) = Self::scan_soup_log(&mut flash);
+        self.flash.replace(flash);
+        last
+    }
+    /// Append `entry`, followed by `ages` if any, to the soup log, erasing
+    /// the page first when it has no room.
+    pub fn log_soup(&mut self, mut entry: SoupEntry, ages: Option<Ages<25>>) {
+        let mut flash = self.flash.take().unwrap();
+        let (_, mut offset) = Self::scan_soup_log(&mut flash);
+        entry.dying = ages.is_some() as u8;
+        let len = SOUP_ENTRY_SIZE + ages.map_or(0, |_| SOUP_AGES_SIZE) + 8;
+        if offset + len > SOUP_LOG_SIZE {
+            flash
+                .erase(SOUP_LOG_ADDR, SOUP_LOG_ADDR + SOUP_LOG_SIZE as u32)
+                .unwrap();
+            offset = 0;
+        }
+        let mut buf = [0u8; SOUP_ENTRY_SIZE + SOUP_AGES_SIZE + 8];
+        unsafe {
+            core::ptr::copy_nonoverlapping::<SoupEntry>(&entry, buf.as_mut_ptr() as *mut _, 1);
+            if let Some(ages) = &ages {
+                core::ptr::copy_nonoverlapping::<Ages<25>>(
+                    ages,
+                    buf[SOUP_ENTRY_SIZE..].as_mut_ptr() as *mut _,
+                    1,
+                );
+            }
+        }
+        buf[len - 8..len - 4].copy_from_slice(&SOUP_LOG_MAGIC.to_le_bytes());
+        let addr = SOUP_LOG_ADDR + offset as u32;
+        flash.write(addr, &buf[..len]).unwrap();
+        self.flash.replace(flash);
+    }
 }
 
 pub struct Game<F> {
     game: LifeGame<25, 16, XorShiftRng>,
+    display: ICN2037Sender,
     keys: KeysReceiver,
     state: State<F>,
+    pixel_mask: PixelMask,
+    hall_of_fame: HallOfFame,
+    /// The board as it was when the game page was left.
+    saved: Option<SavedGame>,
+    /// Board to carry on from on the first visit to the game page after a
+    /// reboot.
+    logged_soup: Option<(SoupEntry, Option<Ages<25>>)>,
 }
 
 impl<F> Game<F>
 where
     F: NorFlash + ReadNorFlash,
 {
-    pub fn new(icn: ICN2037Sender, keys: KeysReceiver, rng: XorShiftRng, state: State<F>) -> Self {
-        let game = LifeGame::<25, 16, _>::new(icn, state.fade_time_ms, rng);
-        Self { game, keys, state }
+    pub fn new(
+        icn: ICN2037Sender,
+        keys: KeysReceiver,
+        rng: XorShiftRng,
+        mut state: State<F>,
+        pixel_mask: PixelMask,
+    ) -> Self {
+        let logged_soup = state.last_soup();
+        let hall_of_fame = load_hall_of_fame(state.flash.as_mut().unwrap());
+        let rng = match &logged_soup {
+            Some((entry, _)) => XorShiftRng::from_seed(entry.rng_seed),
+            None => rng,
+        };
+        let mut game = LifeGame::<25, 16, _>::new(icn.clone(), state.fade_time_ms, rng);
+        game.set_cycle_detection(CYCLE_MAX_PERIOD, CYCLE_REPEATS, true);
+        Self {
+            game,
+            display: icn,
+            keys,
+            state,
+            pixel_mask,
+            hall_of_fame,
+            saved: None,
+            logged_soup,
+        }
+    }
+
+    fn rule(&self) -> Rule {
+        let (name, rule) = RULES[self.state.rule_idx as usize % RULES.len()];
+        Rule::parse(rule).unwrap_or_else(|e| {
+            warn!("bad rule {}: {}", name, e);
+            Rule::default()
+        })
+    }
+
+    /// Keep the board for the next visit to the game page, and log it so
+    /// it can be carried on from after a reboot.
+    fn snapshot(&mut self) {
+        let (board, ages, generation) = self.game.snapshot();
+        let soup_seed = self.game.soup_seed();
+        self.saved = Some(SavedGame {
+            board,
+            ages,
+            generation,
+            soup_seed,
+        });
+        let Some(soup_seed) = soup_seed else {
+            return;
+        };
+        let rng_seed = self.game.checkpoint_rng();
+        self.state.log_soup(
+            SoupEntry {
+                dying: 0,
+                rule_idx: self.state.rule_idx,
+                border_idx: self.state.border_idx,
+                _reserved: 0,
+                soup_seed,
+                generation,
+                rng_seed,
+                board,
+            },
+            ages,
+        );
+    }
+
+    /// Put the soup that just settled into the hall of fame, if it lasted
+    /// long enough.
+    fn record_soup(&mut self) {
+        let Some(seed) = self.game.soup_seed() else {
+            return;
+        };
+        let record = Record {
+            seed,
+            generations: self.game.generation(),
+            rule_idx: self.state.rule_idx,
+            border_idx: self.state.border_idx,
+        };
+        if let Some(rank) = self.hall_of_fame.insert(record) {
+            info!("soup {=u32:08x} is #{} in the hall of fame", seed, rank + 1);
+            save_hall_of_fame(self.state.flash.as_mut().unwrap(), &self.hall_of_fame);
+        }
+    }
+
+    /// Switch to the rule and edge `record` was run with and start its
+    /// soup over.
+    fn replay(&mut self, record: Record) {
+        info!("replay {}", record);
+        self.state.rule_idx = record.rule_idx;
+        self.state.border_idx = record.border_idx;
+        self.game.set_rule(self.rule());
+        self.game.set_boarder_policy(self.boarder_policy());
+        self.game.arrange_soup(record.seed);
+        self.snapshot();
+    }
+
+    fn boarder_policy(&self) -> BoarderPolicy {
+        BoarderPolicy::ALL[self.state.border_idx as usize % BoarderPolicy::ALL.len()]
+    }
+
+    fn stats_mode(&self) -> StatsMode {
+        StatsMode::ALL[self.state.stats_idx as usize % StatsMode::ALL.len()]
+    }
+
+    fn setting_value(&self, setting: Setting) -> &'static str {
+        match setting {
+            Setting::Rule => RULES[self.state.rule_idx as usize % RULES.len()].0,
+            Setting::Border => self.boarder_policy().name(),
+            Setting::Stats => self.stats_mode().name(),
+            Setting::Heat => {
+                if self.state.heat_map {
+                    "AGE"
+                } else {
+                    "OFF"
+                }
+            }
+            Setting::Seed => "EDIT",
+            Setting::Best => match self.hall_of_fame.entries().len() {
+                0 => "NONE",
+                _ => "VIEW",
+            },
+        }
+    }
+
+    fn change_setting(&mut self, setting: Setting) {
+        match setting {
+            Setting::Rule => {
+                self.state.rule_idx = ((self.state.rule_idx as usize + 1) % RULES.len()) as u8;
+                info!("rule: {}", RULES[self.state.rule_idx as usize].1);
+                self.game.set_rule(self.rule());
+            }
+            Setting::Border => {
+                self.state.border_idx =
+                    ((self.state.border_idx as usize + 1) % BoarderPolicy::ALL.len()) as u8;
+                info!("border: {}", self.boarder_policy());
+                self.game.set_boarder_policy(self.boarder_policy());
+            }
+            Setting::Stats => {
+                self.state.stats_idx =
+                    ((self.state.stats_idx as usize + 1) % StatsMode::ALL.len()) as u8;
+            }
+            Setting::Heat => {
+                self.state.heat_map = !self.state.heat_map;
+                self.game.set_heat_map(self.state.heat_map);
+            }
+            // B opens the seed editor or the hall of fame instead
+            Setting::Seed | Setting::Best => {}
+        }
+    }
+
+    fn show_text(&mut self, top: &str, bottom: &str) {
+        if self.display.clear(Gray4::BLACK).is_err() {
+            warn!("display queues full, clear dropped");
+        }
+        let style = MonoTextStyleBuilder::new()
+            .text_color(Gray4::WHITE)
+            .font(&ascii::FONT_4X6)
+            .build();
+        for (text, y) in [(top, 5), (bottom, 13)] {
+            Text::with_alignment(
+                text,
+                Point::new(0, y),
+                style,
+                embedded_graphics::text::Alignment::Left,
+            )
+            .draw(&mut self.display)
+            .unwrap();
+        }
+    }
+
+    /// Show the rank and lifetime of entry `idx` of the hall of fame.
+    fn show_record(&mut self, idx: usize) {
+        let record = self.hall_of_fame.entries()[idx];
+        let mut rank = heapless::String::<4>::new();
+        let mut generations = heapless::String::<12>::new();
+        core::write!(rank, "#{}", idx + 1).ok();
+        core::write!(generations, "{}", record.generations).ok();
+        self.show_text(&rank, &generations);
+    }
+
+    /// Show `seed` as two rows of four hex digits, the one at `cursor`
+    /// brighter than the rest.
+    fn show_seed(&mut self, seed: u32, cursor: usize) {
+        if self.display.clear(Gray4::BLACK).is_err() {
+            warn!("display queues full, clear dropped");
+        }
+        let mut text = heapless::String::<8>::new();
+        core::write!(text, "{:08X}", seed).ok();
+        for (i, c) in text.char_indices() {
+            let color = if i == cursor {
+                Gray4::WHITE
+            } else {
+                Gray4::new(4)
+            };
+            let style = MonoTextStyleBuilder::new()
+                .text_color(color)
+                .font(&ascii::FONT_4X6)
+                .build();
+            let mut buf = [0u8; 4];
+            Text::with_alignment(
+                c.encode_utf8(&mut buf),
+                Point::new(4 * (i % 4) as i32, if i < 4 { 5 } else { 13 }),
+                style,
+                embedded_graphics::text::Alignment::Left,
+            )
+            .draw(&mut self.display)
+            .unwrap();
+        }
+    }
+
+    fn show_setting(&mut self, setting: Setting) {
+        let label = match setting {
+            Setting::Rule => "RULE",
+            Setting::Border => "EDGE",
+            Setting::Stats => "STATS",
+            Setting::Heat => "HEAT",
+            Setting::Seed => "SEED",
+            Setting::Best => "BEST",
+        };
+        self.show_text(label, self.setting_value(setting));
+    }
+
+    /// Keep the cells on masked pixels dead, the driver has had the mask
+    /// since start up.
+    fn apply_pixel_mask(&mut self) {
+        for x in 0..25 {
+            for y in 0..16 {
+                if self.pixel_mask.is_masked(x, y) {
+                    self.game.set_unavailable(x, y, true);
+                }
+            }
+        }
+    }
+
+    async fn draw_diagnostics(&mut self, cursor: (usize, usize)) {
+        for x in 0..25 {
+            for y in 0..16 {
+                let v = if x == cursor.0 || y == cursor.1 { 8 } else { 2 };
+                self.game
+                    .send_message(icn2037::ICN2037Message::SetPixel((x, y, v)))
+                    .await;
+            }
+        }
+    }
+
+    /// Plot `values` as one bar per column, scaled to the largest, with the
+    /// last value in the rightmost column.
+    async fn draw_sparkline(&mut self, values: &[u16]) {
+        let max = values.iter().copied().max().unwrap_or(0).max(1) as usize;
+        for x in 0..25 {
+            let bar = (x + values.len())
+                .checked_sub(25)
+                .map_or(0, |i| (values[i] as usize * 16).div_ceil(max));
+            for y in 0..16 {
+                let v = match 16 - y {
+                    h if h == bar => 15,
+                    h if h < bar => 4,
+                    _ => 0,
+                };
+                self.game
+                    .send_message(icn2037::ICN2037Message::SetPixel((x, y, v)))
+                    .await;
+            }
+        }
+    }
+
+    /// Show how many generations the soup lasted, then its population.
+    async fn show_lifetime(&mut self) {
+        let mut text = heapless::String::<12>::new();
+        core::write!(text, "{}", self.game.generation()).ok();
+        self.show_text("GEN", &text);
+        Timer::after_millis(1500).await;
+        let mut values = [0u16; LIFETIME_LEN];
+        let lifetime = self.game.history().lifetime();
+        values[..lifetime.len()].copy_from_slice(lifetime);
+        let len = lifetime.len();
+        self.draw_sparkline(&values[..len]).await;
+        Timer::after_millis(1500).await;
     }
 
     pub async fn run(&mut self) {
-        self.game.randomly_arrange_patterns();
-        self.game.draw(true).await;
+        self.apply_pixel_mask();
+        self.game.set_rule(self.rule());
+        self.game.set_boarder_policy(self.boarder_policy());
+        self.game.set_heat_map(self.state.heat_map);
         let mut page_inited = false;
-        let mut game_pressed_a = None;
-        let mut game_pressed_b = None;
+        let mut last_snapshot = Instant::now();
+        let mut game_pressed_a: Option<Instant> = None;
+        let mut game_pressed_b: Option<Instant> = None;
         let mut light_d = 1i8;
         let mut light_pressed = None;
+        let mut diag_cursor = (0usize, 0usize);
+        let mut diag_blink = Instant::now();
+        let mut diag_on = false;
+        let mut diag_pressed_a = None;
+        let mut diag_pressed_b = None;
+        let mut diag_mask_changed = false;
+        let mut setting_idx = 0;
+        let mut settings_pressed_a = None;
+        let mut settings_pressed_b = None;
+        let mut seed_edit = 0u32;
+        let mut seed_cursor = 0usize;
+        let mut seed_pressed_a = None;
+        let mut seed_pressed_b = None;
+        let mut hall_idx = 0usize;
+        let mut hall_pressed_a = None;
+        let mut hall_pressed_b = None;
 
         let game_brightnesses = [1, 4, 8, 15];
         let mut game_brightnesses_idx = game_brightnesses
@@ -437,25 +1095,66 @@ where
             match self.state.page {
                 Page::Game => {
                     if !page_inited {
-                        self.game.clear();
+                        let logged_soup = self.logged_soup.take().filter(|(entry, _)| {
+                            entry.rule_idx == self.state.rule_idx
+                                && entry.border_idx == self.state.border_idx
+                        });
+                        if let Some(saved) = self.saved {
+                            info!("resume at generation {}", saved.generation);
+                            self.game.resume(
+                                saved.board,
+                                saved.ages,
+                                saved.generation,
+                                saved.soup_seed,
+                            );
+                        } else if let Some((entry, ages)) = logged_soup {
+                            info!(
+                                "resume soup {=u32:08x} at generation {}",
+                                entry.soup_seed, entry.generation
+                            );
+                            self.game.resume(
+                                entry.board,
+                                ages,
+                                entry.generation,
+                                Some(entry.soup_seed),
+                            );
+                        } else {
+                            self.game.randomly_arrange_patterns();
+                        }
                         self.game
-                            .send_message(icn2037::ICN2037Message::SetBrightness(
-                                self.state.game_brightness,
-                            ))
+                            .send_control(ICN2037Control::SetBrightness(self.state.game_brightness))
                             .await;
                         self.game.set_fade_time(self.state.fade_time_ms);
+                        last_snapshot = Instant::now();
                         page_inited = true;
                     }
-                    self.game.draw(false).await;
+                    if self.stats_mode() == StatsMode::Graph {
+                        let mut values = [0u16; 25];
+                        let mut len = 0;
+                        for (v, p) in values.iter_mut().zip(self.game.history().recent(25)) {
+                            *v = p;
+                            len += 1;
+                        }
+                        self.draw_sparkline(&values[..len]).await;
+                        Timer::after_millis(self.state.fade_time_ms).await;
+                    } else {
+                        self.game.draw(false).await;
+                    }
 
                     match key_event {
                         Ok(KeyEvent::Released(Key::A)) | Ok(KeyEvent::Released(Key::B)) => {
-                            if game_pressed_a.is_some() && game_pressed_b.is_some() {
+                            if let (Some(a), Some(b)) = (game_pressed_a, game_pressed_b) {
                                 game_pressed_a = None;
                                 game_pressed_b = None;
-                                speed_idx = (speed_idx + 1) % speed_list.len();
-                                self.state.fade_time_ms = speed_list[speed_idx];
-                                self.game.set_fade_time(self.state.fade_time_ms);
+                                if Instant::now() - a.min(b) > Duration::from_millis(1000) {
+                                    self.state.page = Page::Settings;
+                                    self.snapshot();
+                                    page_inited = false;
+                                } else {
+                                    speed_idx = (speed_idx + 1) % speed_list.len();
+                                    self.state.fade_time_ms = speed_list[speed_idx];
+                                    self.game.set_fade_time(self.state.fade_time_ms);
+                                }
                                 self.state.save().await;
                             }
                         }
@@ -470,6 +1169,7 @@ where
                             if let Some(pressed) = game_pressed_a {
                                 if Instant::now() - pressed > Duration::from_millis(1000) {
                                     self.state.page = Page::Light;
+                                    self.snapshot();
                                     self.state.save().await;
                                     page_inited = false;
                                 } else {
@@ -478,7 +1178,7 @@ where
                                     self.state.game_brightness =
                                         game_brightnesses[game_brightnesses_idx];
                                     self.game
-                                        .send_message(icn2037::ICN2037Message::SetBrightness(
+                                        .send_control(ICN2037Control::SetBrightness(
                                             self.state.game_brightness,
                                         ))
                                         .await;
@@ -497,25 +1197,43 @@ where
                                     self.state.save().await;
                                 } else {
                                     self.game.randomly_arrange_patterns();
+                                    self.snapshot();
+                                    last_snapshot = Instant::now();
                                 }
                             }
                             game_pressed_b = None;
                         }
                         _ => {}
                     }
-                    if self.game.is_still() {
-                        // break;
-                        info!("re-generate");
+                    if let Some(period) = self.game.is_cycling() {
+                        info!(
+                            "period {} cycle after {} generations of soup {=?}, re-generate",
+                            period,
+                            self.game.generation(),
+                            self.game.soup_seed()
+                        );
+                        self.record_soup();
+                        if self.stats_mode() == StatsMode::Reseed {
+                            self.show_lifetime().await;
+                        }
                         self.game.randomly_arrange_patterns();
+                        self.snapshot();
+                        last_snapshot = Instant::now();
                     }
                     self.game.step_apply();
                     self.game.step();
+                    if matches!(self.state.page, Page::Game)
+                        && Instant::now() - last_snapshot > SOUP_LOG_INTERVAL
+                    {
+                        self.snapshot();
+                        last_snapshot = Instant::now();
+                    }
                 }
                 Page::Light => {
                     if !page_inited {
                         self.game.clear();
                         self.game
-                            .send_message(icn2037::ICN2037Message::SetBrightness(15))
+                            .send_control(ICN2037Control::SetBrightness(15))
                             .await;
                         self.game
                             .send_message(icn2037::ICN2037Message::Fullfill(
@@ -581,6 +1299,218 @@ where
                         _ => {}
                     }
                 }
+                Page::Diagnostics => {
+                    let mut key_event = key_event;
+                    if !page_inited {
+                        self.game.clear();
+                        self.game
+                            .send_control(ICN2037Control::SetBrightness(15))
+                            .await;
+                        self.draw_diagnostics(diag_cursor).await;
+                        // the A press held at power on to get here is still
+                        // queued, only act on keys pressed from now on
+                        key_event = Err(TryReceiveError::Empty);
+                        while self.keys.try_receive().is_ok() {}
+                        diag_pressed_a = None;
+                        diag_pressed_b = None;
+                        page_inited = true;
+                    }
+                    // blink the cursor, masked pixels stay dark
+                    if Instant::now() - diag_blink > Duration::from_millis(300) {
+                        diag_blink = Instant::now();
+                        diag_on = !diag_on;
+                        let (x, y) = diag_cursor;
+                        self.game
+                            .send_message(icn2037::ICN2037Message::SetPixel((
+                                x,
+                                y,
+                                if diag_on { 15 } else { 0 },
+                            )))
+                            .await;
+                    }
+                    match key_event {
+                        Ok(KeyEvent::Pressed(Key::A)) => {
+                            diag_pressed_a = Some(Instant::now());
+                        }
+                        Ok(KeyEvent::Released(Key::A)) => {
+                            if let Some(pressed) = diag_pressed_a {
+                                if Instant::now() - pressed > Duration::from_millis(1000) {
+                                    if diag_mask_changed {
+                                        self.pixel_mask.save(self.state.flash.as_mut().unwrap());
+                                        diag_mask_changed = false;
+                                    }
+                                    self.state.page = Page::Game;
+                                    self.state.save().await;
+                                    page_inited = false;
+                                } else {
+                                    diag_cursor.0 = (diag_cursor.0 + 1) % 25;
+                                    self.draw_diagnostics(diag_cursor).await;
+                                }
+                            }
+                            diag_pressed_a = None;
+                        }
+                        Ok(KeyEvent::Pressed(Key::B)) => {
+                            diag_pressed_b = Some(Instant::now());
+                        }
+                        Ok(KeyEvent::Released(Key::B)) => {
+                            if let Some(pressed) = diag_pressed_b {
+                                if Instant::now() - pressed > Duration::from_millis(1000) {
+                                    diag_cursor.1 = (diag_cursor.1 + 1) % 16;
+                                    self.draw_diagnostics(diag_cursor).await;
+                                } else {
+                                    let (x, y) = diag_cursor;
+                                    let masked = self.pixel_mask.toggle(x, y);
+                                    diag_mask_changed = true;
+                                    info!("pixel ({}, {}) masked: {}", x, y, masked);
+                                    self.game.set_unavailable(x, y, masked);
+                                    self.game
+                                        .send_control(ICN2037Control::MaskPixel((x, y, masked)))
+                                        .await;
+                                    self.draw_diagnostics(diag_cursor).await;
+                                }
+                            }
+                            diag_pressed_b = None;
+                        }
+                        _ => {}
+                    }
+                }
+                Page::Settings => {
+                    if !page_inited {
+                        self.game
+                            .send_control(ICN2037Control::SetBrightness(self.state.game_brightness))
+                            .await;
+                        self.show_setting(SETTINGS[setting_idx]);
+                        page_inited = true;
+                    }
+                    // only act on keys pressed on this page, not the ones
+                    // still held from entering it
+                    match key_event {
+                        Ok(KeyEvent::Pressed(Key::A)) => {
+                            settings_pressed_a = Some(Instant::now());
+                        }
+                        Ok(KeyEvent::Released(Key::A)) => {
+                            if let Some(pressed) = settings_pressed_a {
+                                if Instant::now() - pressed > Duration::from_millis(1000) {
+                                    self.state.page = Page::Game;
+                                    self.state.save().await;
+                                    page_inited = false;
+                                } else {
+                                    setting_idx = (setting_idx + 1) % SETTINGS.len();
+                                    self.show_setting(SETTINGS[setting_idx]);
+                                }
+                            }
+                            settings_pressed_a = None;
+                        }
+                        Ok(KeyEvent::Pressed(Key::B)) => {
+                            settings_pressed_b = Some(Instant::now());
+                        }
+                        Ok(KeyEvent::Released(Key::B)) => {
+                            if let (Some(_), Setting::Seed) =
+                                (settings_pressed_b, SETTINGS[setting_idx])
+                            {
+                                self.state.page = Page::Seed;
+                                page_inited = false;
+                            } else if let (Some(_), Setting::Best) =
+                                (settings_pressed_b, SETTINGS[setting_idx])
+                            {
+                                if !self.hall_of_fame.entries().is_empty() {
+                                    self.state.page = Page::Hall;
+                                    page_inited = false;
+                                }
+                            } else if settings_pressed_b.is_some() {
+                                self.change_setting(SETTINGS[setting_idx]);
+                                self.state.save().await;
+                                self.show_setting(SETTINGS[setting_idx]);
+                            }
+                            settings_pressed_b = None;
+                        }
+                        _ => {}
+                    }
+                }
+                Page::Seed => {
+                    if !page_inited {
+                        seed_edit = self.game.soup_seed().unwrap_or(0);
+                        seed_cursor = 0;
+                        self.show_seed(seed_edit, seed_cursor);
+                        page_inited = true;
+                    }
+                    match key_event {
+                        Ok(KeyEvent::Pressed(Key::A)) => {
+                            seed_pressed_a = Some(Instant::now());
+                        }
+                        Ok(KeyEvent::Released(Key::A)) => {
+                            if let Some(pressed) = seed_pressed_a {
+                                if Instant::now() - pressed > Duration::from_millis(1000) {
+                                    info!("replay soup {=u32:08x}", seed_edit);
+                                    self.game.arrange_soup(seed_edit);
+                                    self.snapshot();
+                                    self.state.page = Page::Game;
+                                    self.state.save().await;
+                                    page_inited = false;
+                                } else {
+                                    seed_cursor = (seed_cursor + 1) % 8;
+                                    self.show_seed(seed_edit, seed_cursor);
+                                }
+                            }
+                            seed_pressed_a = None;
+                        }
+                        Ok(KeyEvent::Pressed(Key::B)) => {
+                            seed_pressed_b = Some(Instant::now());
+                        }
+                        Ok(KeyEvent::Released(Key::B)) => {
+                            if let Some(pressed) = seed_pressed_b {
+                                if Instant::now() - pressed > Duration::from_millis(1000) {
+                                    self.state.page = Page::Settings;
+                                    page_inited = false;
+                                } else {
+                                    let shift = 28 - 4 * seed_cursor;
+                                    let digit = (seed_edit >> shift).wrapping_add(1) & 0xf;
+                                    seed_edit = seed_edit & !(0xf << shift) | digit << shift;
+                                    self.show_seed(seed_edit, seed_cursor);
+                                }
+                            }
+                            seed_pressed_b = None;
+                        }
+                        _ => {}
+                    }
+                }
+                Page::Hall => {
+                    if !page_inited {
+                        hall_idx = 0;
+                        self.show_record(hall_idx);
+                        page_inited = true;
+                    }
+                    match key_event {
+                        Ok(KeyEvent::Pressed(Key::A)) => {
+                            hall_pressed_a = Some(Instant::now());
+                        }
+                        Ok(KeyEvent::Released(Key::A)) => {
+                            if let Some(pressed) = hall_pressed_a {
+                                if Instant::now() - pressed > Duration::from_millis(1000) {
+                                    self.state.page = Page::Settings;
+                                    page_inited = false;
+                                } else {
+                                    hall_idx = (hall_idx + 1) % self.hall_of_fame.entries().len();
+                                    self.show_record(hall_idx);
+                                }
+                            }
+                            hall_pressed_a = None;
+                        }
+                        Ok(KeyEvent::Pressed(Key::B)) => {
+                            hall_pressed_b = Some(Instant::now());
+                        }
+                        Ok(KeyEvent::Released(Key::B)) => {
+                            if hall_pressed_b.is_some() {
+                                self.replay(self.hall_of_fame.entries()[hall_idx]);
+                                self.state.page = Page::Game;
+                                self.state.save().await;
+                                page_inited = false;
+                            }
+                            hall_pressed_b = None;
+                        }
+                        _ => {}
+                    }
+                }
             }
             Timer::after_millis(1).await;
         }
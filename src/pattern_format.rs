@@ -0,0 +1,413 @@
+#![allow(dead_code)]
+
+//! Parsers for the Golly RLE and plaintext `.cells` pattern formats, as
+//! found on LifeWiki.
+//!
+//! Both are `const fn` so patterns can be turned into a `PatternBuf` at
+//! compile time as well as from bytes received at runtime.
+
+/// Largest pattern a `PatternBuf` holds, in either direction.
+pub const MAX_SIZE: usize = 32;
+/// Longest rulestring kept from an RLE header.
+pub const MAX_RULE: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatternError {
+    /// Unexpected character in the pattern or header.
+    Syntax,
+    /// Wider or taller than `MAX_SIZE`, or than the RLE header says.
+    TooLarge,
+    /// An RLE pattern without its `x = .., y = ..` header.
+    Header,
+}
+
+/// One of the 8 rotations and reflections of a pattern.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+    Identity,
+    /// Quarter turn clockwise.
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    /// Mirrored left to right.
+    FlipX,
+    /// Mirrored top to bottom.
+    FlipY,
+    /// Mirrored along the main diagonal, x and y swapped.
+    Transpose,
+    /// Mirrored along the other diagonal.
+    AntiTranspose,
+}
+
+impl Orientation {
+    pub const ALL: [Orientation; 8] = [
+        Orientation::Identity,
+        Orientation::Rotate90,
+        Orientation::Rotate180,
+        Orientation::Rotate270,
+        Orientation::FlipX,
+        Orientation::FlipY,
+        Orientation::Transpose,
+        Orientation::AntiTranspose,
+    ];
+
+    /// Whether width and height trade places.
+    pub const fn swaps_axes(&self) -> bool {
+        matches!(
+            self,
+            Orientation::Rotate90
+                | Orientation::Rotate270
+                | Orientation::Transpose
+                | Orientation::AntiTranspose
+        )
+    }
+
+    /// Where a direction such as a spaceship velocity points afterwards.
+    pub const fn apply(&self, dx: i32, dy: i32) -> (i32, i32) {
+        match self {
+            Orientation::Identity => (dx, dy),
+            Orientation::Rotate90 => (-dy, dx),
+            Orientation::Rotate180 => (-dx, -dy),
+            Orientation::Rotate270 => (dy, -dx),
+            Orientation::FlipX => (-dx, dy),
+            Orientation::FlipY => (dx, -dy),
+            Orientation::Transpose => (dy, dx),
+            Orientation::AntiTranspose => (-dy, -dx),
+        }
+    }
+}
+
+/// A parsed pattern, one bit per cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PatternBuf {
+    pub width: u8,
+    pub height: u8,
+    /// Row `y` of the pattern, bit `x` for column `x`.
+    pub rows: [u32; MAX_SIZE],
+    rule: [u8; MAX_RULE],
+    rule_len: u8,
+}
+
+impl PatternBuf {
+    pub const fn empty() -> Self {
+        Self {
+            width: 0,
+            height: 0,
+            rows: [0; MAX_SIZE],
+            rule: [0; MAX_RULE],
+            rule_len: 0,
+        }
+    }
+
+    pub const fn get(&self, x: usize, y: usize) -> bool {
+        x < self.width as usize && y < self.height as usize && self.rows[y] & (1 << x) != 0
+    }
+
+    /// Rulestring from the RLE header, if there was one.
+    pub fn rule(&self) -> Option<&str> {
+        if self.rule_len == 0 {
+            return None;
+        }
+        core::str::from_utf8(&self.rule[..self.rule_len as usize]).ok()
+    }
+
+    pub const fn population(&self) -> u32 {
+        let mut n = 0;
+        let mut y = 0;
+        while y < MAX_SIZE {
+            n += self.rows[y].count_ones();
+            y += 1;
+        }
+        n
+    }
+
+    /// Parse a pattern in either format, telling them apart by the `x = `
+    /// header only RLE has.
+    pub const fn parse(src: &[u8]) -> Result<Self, PatternError> {
+        if find_header(src).is_some() {
+            parse_rle(src)
+        } else {
+            parse_cells(src)
+        }
+    }
+
+    /// `parse` for constants, failing the build on a bad pattern.
+    pub const fn from_bytes(src: &[u8]) -> Self {
+        match Self::parse(src) {
+            Ok(p) => p,
+            Err(_) => panic!("bad pattern"),
+        }
+    }
+
+    /// The pattern turned or mirrored, still starting at the top left.
+    pub const fn oriented(&self, orientation: Orientation) -> Self {
+        let (w, h) = (self.width as i32, self.height as i32);
+        let mut p = Self::empty();
+        p.rule = self.rule;
+        p.rule_len = self.rule_len;
+        if orientation.swaps_axes() {
+            p.width = self.height;
+            p.height = self.width;
+        } else {
+            p.width = self.width;
+            p.height = self.height;
+        }
+        // the corner the pattern ends up growing from
+        let (ox, oy) = orientation.apply(w - 1, h - 1);
+        let (ox, oy) = (if ox < 0 { -ox } else { 0 }, if oy < 0 { -oy } else { 0 });
+        let mut y = 0;
+        while y < h {
+            let mut x = 0;
+            while x < w {
+                if self.get(x as usize, y as usize) {
+                    let (tx, ty) = orientation.apply(x, y);
+                    p.rows[(ty + oy) as usize] |= 1 << (tx + ox);
+                }
+                x += 1;
+            }
+            y += 1;
+        }
+        p
+    }
+
+    const fn set(&mut self, x: usize, y: usize) -> Result<(), PatternError> {
+        if x >= MAX_SIZE || y >= MAX_SIZE {
+            return Err(PatternError::TooLarge);
+        }
+        self.rows[y] |= 1 << x;
+        Ok(())
+    }
+}
+
+/// Start of the `x = ` header line, skipping `#` comment lines.
+const fn find_header(src: &[u8]) -> Option<usize> {
+    let mut i = 0;
+    while i < src.len() {
+        i = skip_blank(src, i);
+        if i >= src.len() {
+            return None;
+        }
+        match src[i] {
+            b'#' => i = next_line(src, i),
+            b'x' => return Some(i),
+            _ => return None,
+        }
+    }
+    None
+}
+
+const fn next_line(src: &[u8], mut i: usize) -> usize {
+    while i < src.len() && src[i] != b'\n' {
+        i += 1;
+    }
+    i + 1
+}
+
+const fn skip_blank(src: &[u8], mut i: usize) -> usize {
+    while i < src.len() && src[i].is_ascii_whitespace() {
+        i += 1;
+    }
+    i
+}
+
+/// Parse a decimal number at `i`, returning it and the index after it.
+/// Anything above `MAX_SIZE` comes back as `MAX_SIZE + 1`, which is too
+/// large for every caller, so long digit strings cannot overflow.
+const fn number(src: &[u8], mut i: usize) -> Option<(usize, usize)> {
+    let start = i;
+    let mut n = 0;
+    while i < src.len() && src[i].is_ascii_digit() {
+        if n <= MAX_SIZE {
+            n = n * 10 + (src[i] - b'0') as usize;
+        }
+        i += 1;
+    }
+    if n > MAX_SIZE {
+        n = MAX_SIZE + 1;
+    }
+    if i == start {
+        None
+    } else {
+        Some((n, i))
+    }
+}
+
+/// Parse an RLE pattern such as
+///
+/// ```text
+/// #N Glider
+/// x = 3, y = 3, rule = B3/S23
+/// bob$2bo$3o!
+/// ```
+///
+/// Dead cells are `b` or `.`, live cells `o` or `A`; the other letters of
+/// multi-state RLE are taken as dead.
+pub const fn parse_rle(src: &[u8]) -> Result<PatternBuf, PatternError> {
+    let mut p = PatternBuf::empty();
+    let Some(mut i) = find_header(src) else {
+        return Err(PatternError::Header);
+    };
+    let (mut width, mut height) = (0, 0);
+    // header fields, `key = value` separated by commas
+    while i < src.len() && src[i] != b'\n' {
+        i = skip_blank(src, i);
+        // a trailing comma or a cut off header
+        if i >= src.len() {
+            return Err(PatternError::Syntax);
+        }
+        let key = src[i];
+        i = skip_blank(src, i + 1);
+        if key == b'r' {
+            // rule = .., skip the rest of the key
+            while i < src.len() && src[i] != b'=' && src[i] != b'\n' {
+                i += 1;
+            }
+        }
+        if i >= src.len() || src[i] != b'=' {
+            return Err(PatternError::Syntax);
+        }
+        i += 1;
+        while i < src.len() && (src[i] == b' ' || src[i] == b'\t') {
+            i += 1;
+        }
+        match key {
+            b'x' | b'y' => {
+                let Some((n, next)) = number(src, i) else {
+                    return Err(PatternError::Syntax);
+                };
+                if n > MAX_SIZE {
+                    return Err(PatternError::TooLarge);
+                }
+                if key == b'x' {
+                    width = n;
+                } else {
+                    height = n;
+                }
+                i = next;
+            }
+            b'r' => {
+                while i < src.len() && !src[i].is_ascii_whitespace() && src[i] != b',' {
+                    if p.rule_len as usize == MAX_RULE {
+                        return Err(PatternError::TooLarge);
+                    }
+                    p.rule[p.rule_len as usize] = src[i];
+                    p.rule_len += 1;
+                    i += 1;
+                }
+            }
+            _ => return Err(PatternError::Syntax),
+        }
+        while i < src.len() && (src[i] == b' ' || src[i] == b'\t' || src[i] == b'\r') {
+            i += 1;
+        }
+        if i < src.len() && src[i] == b',' {
+            i += 1;
+            // another field has to follow the comma
+            if skip_blank(src, i) >= src.len() {
+                return Err(PatternError::Syntax);
+            }
+        }
+    }
+    p.width = width as u8;
+    p.height = height as u8;
+
+    let (mut x, mut y) = (0, 0);
+    while i < src.len() {
+        let c = src[i];
+        if c.is_ascii_whitespace() {
+            i += 1;
+            continue;
+        }
+        if c == b'!' {
+            break;
+        }
+        let mut run = 1;
+        if let Some((n, next)) = number(src, i) {
+            run = n;
+            i = next;
+            if i >= src.len() {
+                return Err(PatternError::Syntax);
+            }
+        }
+        match src[i] {
+            b'$' => {
+                y += run;
+                x = 0;
+            }
+            b'o' | b'A' => {
+                let mut k = 0;
+                while k < run {
+                    if let Err(e) = p.set(x + k, y) {
+                        return Err(e);
+                    }
+                    k += 1;
+                }
+                x += run;
+            }
+            b'b' | b'.' | b'B'..=b'Z' => x += run,
+            _ => return Err(PatternError::Syntax),
+        }
+        if x > width || (y >= height && x > 0) {
+            return Err(PatternError::TooLarge);
+        }
+        i += 1;
+    }
+    Ok(p)
+}
+
+const fn is_syntax_error(src: &[u8]) -> bool {
+    matches!(parse_rle(src), Err(PatternError::Syntax))
+}
+// truncated headers and trailing commas are errors, not reads past the end
+const _: () = assert!(
+    is_syntax_error(b"x = 3, ")
+        && is_syntax_error(b"x = 3,")
+        && is_syntax_error(b"x")
+        && is_syntax_error(b"x =")
+        && is_syntax_error(b"x = 3, y"),
+    "truncated RLE header accepted"
+);
+
+/// Parse a plaintext pattern, `!` comment lines followed by rows of `.`
+/// for dead and `O` (or `*`) for live cells.
+pub const fn parse_cells(src: &[u8]) -> Result<PatternBuf, PatternError> {
+    let mut p = PatternBuf::empty();
+    let (mut width, mut height) = (0, 0);
+    let mut y = 0;
+    let mut i = 0;
+    while i < src.len() {
+        if src[i] == b'!' {
+            i = next_line(src, i);
+            continue;
+        }
+        let mut x = 0;
+        while i < src.len() && src[i] != b'\n' {
+            match src[i] {
+                b'O' | b'*' => {
+                    if let Err(e) = p.set(x, y) {
+                        return Err(e);
+                    }
+                    x += 1;
+                }
+                b'.' => x += 1,
+                b'\r' | b' ' | b'\t' => {}
+                _ => return Err(PatternError::Syntax),
+            }
+            i += 1;
+        }
+        i += 1;
+        y += 1;
+        if x > 0 {
+            if x > width {
+                width = x;
+            }
+            height = y;
+        }
+    }
+    if width > MAX_SIZE || height > MAX_SIZE {
+        return Err(PatternError::TooLarge);
+    }
+    p.width = width as u8;
+    p.height = height as u8;
+    Ok(p)
+}
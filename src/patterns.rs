@@ -1,122 +1,85 @@
-#[rustfmt::skip]
+use crate::pattern_format::PatternBuf;
 
-mod all {
-    pub const PATTERN_STABLE_BLOCK: &[&str] = &[
-        "XX", 
-        "XX"
-    ];
-    pub const PATTERN_STABLE_LOAF: &[&str] = &[
-        " XX ", 
-        "X  X", 
-        " X X", 
-        "  X ", 
-    ];
-    pub const PATTERN_STABLE_BEEHIVE: &[&str] = &[
-        " XX ", 
-        "X  X", 
-        " XX ", 
-    ];
-    pub const PATTERN_STABLE_SHIP: &[&str] = &[
-        " XXX", 
-        "X  X", 
-        "XXX ", 
-    ];
-    pub const PATTERN_STABLE_BOAT: &[&str] = &[
-        "XX ", 
-        "X X", 
-        " X ", 
-    ];
-    pub const PATTERN_STABLE_FLOWER: &[&str] = &[
-        " X ", 
-        "X X", 
-        " X ", 
-    ];
-    pub const PATTERN_STABLE_POND: &[&str] = &[
-        " X ", 
-        "X X", 
-        " X ", 
-    ];
-    pub const PATTERN_STABLE_LIST: &[&[&str]] = &[
-        PATTERN_STABLE_BLOCK,
-        PATTERN_STABLE_LOAF,
-        PATTERN_STABLE_BEEHIVE,
-        PATTERN_STABLE_SHIP,
-        PATTERN_STABLE_BOAT,
-        PATTERN_STABLE_FLOWER,
-        PATTERN_STABLE_POND,
-    ];
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Category {
+    StillLife,
+    Oscillator,
+    Spaceship,
+}
 
-    pub const PATTERN_CLOCK_BLINKER: &[&str] = &[
-        "XXX", 
-    ];
-    pub const PATTERN_CLOCK_TOAD: &[&str] = &[
-        " XXX",
-        "XXX ",
-    ];
-    pub const PATTERN_CLOCK_TRAFIC_LIGHT: &[&str] = &[
-        "  XXX  ", 
-        "       ",
-        "X     X",
-        "X     X",
-        "X     X",
-        "       ",
-        "  XXX  ",
-    ];
-    pub const PATTERN_CLOCK_BEACON: &[&str] = &[
-        "XX  ", 
-        "XX  ", 
-        "  XX", 
-        "  XX", 
-    ];
-    pub const PATTERN_CLOCK_PULSAR: &[&str] = &[
-        "  XXX   XXX  ", 
-        "             ",
-        "X    X X    X", 
-        "X    X X    X", 
-        "X    X X    X", 
-        "  XXX   XXX  ", 
-        "             ",
-        "  XXX   XXX  ", 
-        "X    X X    X", 
-        "X    X X    X", 
-        "X    X X    X", 
-        "             ",
-        "  XXX   XXX  ", 
-    ];
-    pub const PATTERN_CLOCK_I_COLUMN: &[&str] = &[
-        "XXX",
-        "X X",
-        "XXX",
-        "XXX",
-        "XXX",
-        "XXX",
-        "X X",
-        "XXX",
-    ];
-    pub const PATTERN_CLOCK_LIST: &[&[&str]] = &[
-        PATTERN_CLOCK_BLINKER,
-        PATTERN_CLOCK_TOAD,
-        PATTERN_CLOCK_TRAFIC_LIGHT,
-        PATTERN_CLOCK_BEACON,
-        PATTERN_CLOCK_PULSAR,
-        PATTERN_CLOCK_I_COLUMN,
+impl Category {
+    pub const ALL: [Category; 3] = [
+        Category::StillLife,
+        Category::Oscillator,
+        Category::Spaceship,
     ];
+}
 
-    pub const PATTERN_FLY_GLIDER: &[&str] = &[
-        " X ",
-        "  X",
-        "XXX",
-    ];
-    pub const PATTERN_FLY_LIGHTWEIGHT_SPACESHIP: &[&str] = &[
-        "X  X ",
-        "    X",
-        "X   X",
-        " XXXX",
-    ];
-    pub const PATTERN_FLY_LIST: &[&[&str]] = &[
-        PATTERN_FLY_GLIDER,
-        PATTERN_FLY_LIGHTWEIGHT_SPACESHIP,
-    ];
+/// A pattern from the `patterns/` directory, packed and run by `build.rs`.
+#[derive(Debug, Clone, Copy)]
+pub struct Pattern {
+    pub name: &'static str,
+    /// The directory the pattern was found in, checked against its period.
+    pub category: Category,
+    /// Generations until the pattern is back to its first phase.
+    pub period: u8,
+    pub width: u8,
+    pub height: u8,
+    /// Cells moved in x and y over one period, zero unless a spaceship.
+    pub velocity: (i8, i8),
+    /// First byte of the pattern in `PATTERN_DATA`, which holds the cells
+    /// row by row, one bit each.
+    offset: u16,
 }
 
-pub use all::*;
+impl Pattern {
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        let i = y * self.width as usize + x;
+        PATTERN_DATA[self.offset as usize + i / 8] & (1 << (i % 8)) != 0
+    }
+
+    pub fn unpack(&self) -> PatternBuf {
+        let mut p = PatternBuf::empty();
+        p.width = self.width;
+        p.height = self.height;
+        for y in 0..self.height as usize {
+            for x in 0..self.width as usize {
+                if self.get(x, y) {
+                    p.rows[y] |= 1 << x;
+                }
+            }
+        }
+        p
+    }
+}
+
+include!(concat!(env!("OUT_DIR"), "/patterns.rs"));
+
+/// Every pattern must fill exactly its `width * height` bits of
+/// `PATTERN_DATA`, up to where the next one starts.
+const fn check_rectangular(patterns: &[Pattern], len: usize) -> bool {
+    let mut i = 0;
+    while i < patterns.len() {
+        let p = &patterns[i];
+        let end = if i + 1 < patterns.len() {
+            patterns[i + 1].offset as usize
+        } else {
+            len
+        };
+        let bytes = (p.width as usize * p.height as usize).div_ceil(8);
+        if p.width == 0 || p.height == 0 || p.offset as usize + bytes != end {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+const _: () = assert!(
+    check_rectangular(PATTERNS, PATTERN_DATA.len()),
+    "pattern table is not rectangular"
+);
+
+pub fn patterns_in(category: Category) -> impl Iterator<Item = &'static Pattern> {
+    PATTERNS.iter().filter(move |p| p.category == category)
+}
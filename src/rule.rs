@@ -0,0 +1,285 @@
+#![allow(dead_code)]
+
+use crate::lifegame::CellState;
+
+/// Life-like (outer totalistic) rule, parsed from a rulestring.
+///
+/// With more than two `states` it is a Generations rule: a live cell that
+/// does not survive goes through `states - 2` dying states before it is dead,
+/// and dying cells neither count as neighbours nor can be born again.
+///
+/// Larger than Life rules count over a `range` of more than one cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Rule {
+    /// Bit `n` set when a dead cell with `n` live neighbours is born.
+    pub birth: u64,
+    /// Bit `n` set when a live cell with `n` live neighbours survives.
+    pub survival: u64,
+    /// Number of cell states including dead and alive, 2 for life-like rules.
+    pub states: u8,
+    pub neighbourhood: Neighbourhood,
+    /// Distance of the farthest neighbour, 1 except for Larger than Life.
+    pub range: u8,
+    /// Whether the cell itself counts as its own neighbour.
+    pub middle: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Neighbourhood {
+    /// The surrounding square, 8 cells at range 1.
+    Moore,
+    /// Cells within a Manhattan distance, 4 cells at range 1.
+    VonNeumann,
+    /// Hexagonal grid emulated on the square one by leaving out the
+    /// top-right and bottom-left corners as Golly does, 6 cells at range 1.
+    Hexagonal,
+}
+
+impl Neighbourhood {
+    /// Whether the cell at offset (`dx`, `dy`) is within `range`.
+    pub fn contains(&self, dx: i32, dy: i32, range: i32) -> bool {
+        if dx.abs() > range || dy.abs() > range {
+            return false;
+        }
+        match self {
+            Neighbourhood::Moore => true,
+            Neighbourhood::VonNeumann => dx.abs() + dy.abs() <= range,
+            Neighbourhood::Hexagonal => (dx - dy).abs() <= range,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RuleError {
+    /// Not a `B.../S...` or `S.../B...` rulestring.
+    Syntax,
+    /// A neighbour count out of range for the neighbourhood.
+    Count,
+    /// A Generations state count below 2 or above `MAX_STATES`.
+    States,
+    /// A Larger than Life range above `MAX_RANGE`.
+    Range,
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        Self::CONWAY
+    }
+}
+
+impl Rule {
+    pub const CONWAY: Rule = Rule {
+        birth: 1 << 3,
+        survival: 1 << 2 | 1 << 3,
+        states: 2,
+        neighbourhood: Neighbourhood::Moore,
+        range: 1,
+        middle: false,
+    };
+
+    /// Enough dying states to fade through every gray level.
+    pub const MAX_STATES: u8 = 16;
+    /// Keeps every count of a Moore neighbourhood within the `u64` masks.
+    pub const MAX_RANGE: u8 = 3;
+
+    /// Parse `B3/S23` style rulestrings, case insensitive, as well as the
+    /// older `23/3` survival/birth notation. A third field gives the state
+    /// count of a Generations rule, as in `B2/S/C3` or `345/2/4`, and a `V`
+    /// or `H` suffix picks the von Neumann or hexagonal neighbourhood.
+    ///
+    /// Larger than Life rules use the `R2,C0,M1,S2..3,B3..4,NM` notation.
+    pub fn parse(s: &str) -> Result<Self, RuleError> {
+        let s = s.trim();
+        if s.contains(',') {
+            return Self::parse_ltl(s);
+        }
+        let (s, neighbourhood) = match s.chars().last().map(|c| c.to_ascii_lowercase()) {
+            Some('v') => (&s[..s.len() - 1], Neighbourhood::VonNeumann),
+            Some('h') => (&s[..s.len() - 1], Neighbourhood::Hexagonal),
+            _ => (s, Neighbourhood::Moore),
+        };
+        let (a, rest) = s.split_once('/').ok_or(RuleError::Syntax)?;
+        let (b, states) = match rest.split_once('/') {
+            Some((b, c)) => (b, states(c)?),
+            None => (rest, 2),
+        };
+        let (birth, survival) = match (prefix(a), prefix(b)) {
+            (Some('b'), Some('s')) => (&a[1..], &b[1..]),
+            (Some('s'), Some('b')) => (&b[1..], &a[1..]),
+            (None, None) => (b, a),
+            _ => return Err(RuleError::Syntax),
+        };
+        let mut rule = Self {
+            neighbourhood,
+            states,
+            ..Self::CONWAY
+        };
+        let max = rule.size();
+        rule.birth = counts(birth, max)?;
+        rule.survival = counts(survival, max)?;
+        Ok(rule)
+    }
+
+    fn parse_ltl(s: &str) -> Result<Self, RuleError> {
+        let mut rule = Self {
+            birth: 0,
+            survival: 0,
+            ..Self::CONWAY
+        };
+        let (mut birth, mut survival) = (None, None);
+        for field in s.split(',') {
+            let field = field.trim();
+            let Some(key) = field.chars().next() else {
+                return Err(RuleError::Syntax);
+            };
+            let value = &field[key.len_utf8()..];
+            match key.to_ascii_lowercase() {
+                'r' => rule.range = value.parse().map_err(|_| RuleError::Syntax)?,
+                // C0 and C1 both mean a plain two state rule
+                'c' => {
+                    rule.states = match value {
+                        "0" | "1" => 2,
+                        _ => states(value)?,
+                    }
+                }
+                'm' => rule.middle = value == "1",
+                's' => survival = Some(value),
+                'b' => birth = Some(value),
+                'n' => {
+                    rule.neighbourhood = match value {
+                        "M" | "m" => Neighbourhood::Moore,
+                        "N" | "n" => Neighbourhood::VonNeumann,
+                        "H" | "h" => Neighbourhood::Hexagonal,
+                        _ => return Err(RuleError::Syntax),
+                    }
+                }
+                _ => return Err(RuleError::Syntax),
+            }
+        }
+        if rule.range == 0 || rule.range > Self::MAX_RANGE {
+            return Err(RuleError::Range);
+        }
+        let max = rule.size();
+        rule.birth = count_range(birth.ok_or(RuleError::Syntax)?, max)?;
+        rule.survival = count_range(survival.ok_or(RuleError::Syntax)?, max)?;
+        Ok(rule)
+    }
+
+    /// Number of cells counted as neighbours.
+    pub fn size(&self) -> u32 {
+        let r = self.range as i32;
+        let mut n = 0;
+        for dx in -r..=r {
+            for dy in -r..=r {
+                if self.is_neighbour(dx, dy) {
+                    n += 1;
+                }
+            }
+        }
+        n
+    }
+
+    /// Whether the cell at offset (`dx`, `dy`) counts as a neighbour.
+    pub fn is_neighbour(&self, dx: i32, dy: i32) -> bool {
+        (self.middle || dx != 0 || dy != 0)
+            && self.neighbourhood.contains(dx, dy, self.range as i32)
+    }
+
+    /// State of a cell in the next generation, given its live neighbours.
+    pub fn next(&self, cell: CellState, count: usize) -> CellState {
+        match cell {
+            CellState::Dead if self.birth & (1 << count) != 0 => CellState::Alive,
+            CellState::Dead => CellState::Dead,
+            CellState::Alive if self.survival & (1 << count) != 0 => CellState::Alive,
+            CellState::Alive | CellState::Dying(_) => self.decay(cell),
+        }
+    }
+
+    /// The state after `cell` when it does not stay alive.
+    fn decay(&self, cell: CellState) -> CellState {
+        let age = match cell {
+            CellState::Dying(age) => age + 1,
+            _ => 1,
+        };
+        if age + 2 > self.states {
+            CellState::Dead
+        } else {
+            CellState::Dying(age)
+        }
+    }
+
+    /// Gray level of a state, dying cells fade out towards dead.
+    pub fn gray_level(&self, cell: CellState) -> u8 {
+        match cell {
+            CellState::Dead => 0,
+            CellState::Alive => 15,
+            CellState::Dying(age) => {
+                let n = self.states - 1;
+                ((n - age) as u16 * 15 / n as u16) as u8
+            }
+        }
+    }
+}
+
+fn prefix(s: &str) -> Option<char> {
+    s.chars()
+        .next()
+        .map(|c| c.to_ascii_lowercase())
+        .filter(|c| *c == 'b' || *c == 's')
+}
+
+fn states(s: &str) -> Result<u8, RuleError> {
+    let s = match s.chars().next().map(|c| c.to_ascii_lowercase()) {
+        Some('c') | Some('g') => &s[1..],
+        _ => s,
+    };
+    let n: u8 = s.parse().map_err(|_| RuleError::Syntax)?;
+    if !(2..=Rule::MAX_STATES).contains(&n) {
+        return Err(RuleError::States);
+    }
+    Ok(n)
+}
+
+fn counts(s: &str, max: u32) -> Result<u64, RuleError> {
+    let mut r = 0;
+    for c in s.chars() {
+        let n = c.to_digit(10).ok_or(RuleError::Syntax)?;
+        if n > max {
+            return Err(RuleError::Count);
+        }
+        r |= 1 << n;
+    }
+    Ok(r)
+}
+
+/// A `min..max` count range of a Larger than Life rule, or a single count.
+fn count_range(s: &str, max: u32) -> Result<u64, RuleError> {
+    let (lo, hi) = s.split_once("..").unwrap_or((s, s));
+    let lo: u32 = lo.parse().map_err(|_| RuleError::Syntax)?;
+    let hi: u32 = hi.parse().map_err(|_| RuleError::Syntax)?;
+    if lo > hi || hi > max {
+        return Err(RuleError::Count);
+    }
+    Ok((lo..=hi).fold(0, |r, n| r | 1 << n))
+}
+
+/// Rules to cycle through from the settings page, as (short name, rulestring).
+pub const RULES: &[(&str, &str)] = &[
+    ("LIFE", "B3/S23"),
+    ("HILIFE", "B36/S23"),
+    ("SEEDS", "B2/S"),
+    ("DAYNGT", "B3678/S34678"),
+    ("2X2", "B36/S125"),
+    ("MAZE", "B3/S12345"),
+    ("MOVE", "B368/S245"),
+    ("DRYLIF", "B37/S23"),
+    ("BRAIN", "/2/3"),
+    ("STARWR", "345/2/4"),
+    ("STICKS", "3456/2/6"),
+    ("HEXLIF", "B2/S34H"),
+    ("VONNEU", "B1/S1V"),
+    ("MAJORT", "R3,C0,M1,S25..49,B25..49,NM"),
+];
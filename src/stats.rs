@@ -0,0 +1,116 @@
+#![allow(dead_code)]
+
+/// Generations kept in `History`.
+pub const HISTORY_LEN: usize = 64;
+/// Samples of a whole soup's population, one per panel column.
+pub const LIFETIME_LEN: usize = 25;
+
+/// Counts of one generation.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct GenStats {
+    pub generation: u32,
+    pub population: u16,
+    pub births: u16,
+    pub deaths: u16,
+}
+
+/// The last `HISTORY_LEN` generations, and the population of every
+/// generation since the last reset squeezed into `LIFETIME_LEN` samples.
+pub struct History {
+    entries: [GenStats; HISTORY_LEN],
+    head: usize,
+    len: usize,
+    lifetime: [u16; LIFETIME_LEN],
+    lifetime_len: usize,
+    /// Generations per lifetime sample, doubled whenever the samples fill up.
+    stride: u32,
+    /// Population summed over the sample being filled.
+    pending: u32,
+    pending_len: u32,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl History {
+    pub const fn new() -> Self {
+        Self {
+            entries: [GenStats {
+                generation: 0,
+                population: 0,
+                births: 0,
+                deaths: 0,
+            }; HISTORY_LEN],
+            head: 0,
+            len: 0,
+            lifetime: [0; LIFETIME_LEN],
+            lifetime_len: 0,
+            stride: 1,
+            pending: 0,
+            pending_len: 0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    pub fn push(&mut self, stats: GenStats) {
+        self.entries[self.head] = stats;
+        self.head = (self.head + 1) % HISTORY_LEN;
+        self.len = (self.len + 1).min(HISTORY_LEN);
+
+        self.pending += stats.population as u32;
+        self.pending_len += 1;
+        if self.pending_len < self.stride {
+            return;
+        }
+        if self.lifetime_len == LIFETIME_LEN {
+            // halve the resolution, averaging neighbouring samples
+            for i in 0..LIFETIME_LEN / 2 {
+                let (a, b) = (self.lifetime[2 * i] as u32, self.lifetime[2 * i + 1] as u32);
+                self.lifetime[i] = ((a + b) / 2) as u16;
+            }
+            if LIFETIME_LEN % 2 == 1 {
+                // the odd sample out goes back into the one being filled
+                self.pending += self.lifetime[LIFETIME_LEN - 1] as u32 * self.stride;
+                self.pending_len += self.stride;
+            }
+            self.lifetime_len = LIFETIME_LEN / 2;
+            self.stride *= 2;
+            if self.pending_len < self.stride {
+                return;
+            }
+        }
+        self.lifetime[self.lifetime_len] = (self.pending / self.pending_len) as u16;
+        self.lifetime_len += 1;
+        self.pending = 0;
+        self.pending_len = 0;
+    }
+
+    pub fn latest(&self) -> Option<GenStats> {
+        (self.len > 0).then(|| self.entries[(self.head + HISTORY_LEN - 1) % HISTORY_LEN])
+    }
+
+    /// The kept generations, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = &GenStats> {
+        (HISTORY_LEN + self.head - self.len..HISTORY_LEN + self.head)
+            .map(|i| &self.entries[i % HISTORY_LEN])
+    }
+
+    /// Population of the last `n` generations, oldest first.
+    pub fn recent(&self, n: usize) -> impl Iterator<Item = u16> + '_ {
+        self.iter()
+            .skip(self.len.saturating_sub(n))
+            .map(|s| s.population)
+    }
+
+    /// Population over the whole soup, oldest first.
+    pub fn lifetime(&self) -> &[u16] {
+        &self.lifetime[..self.lifetime_len]
+    }
+}